@@ -42,9 +42,12 @@ struct Args {
 
 fn main() -> Result<()> {
     let args = Args::parse();
-    let pem = fs::read_to_string(&args.private_key)?;
+    let pem = fs::read(&args.private_key)?;
     let full_identifier = format!("{}.{}", &args.account_identifier, &args.username);
-    let jwt = snowflake_jwt::generate_jwt_token(&pem, &full_identifier)?;
+    let private_key = snowflake_jwt::load_private_key(&pem, None)?;
+    // generator caches the token, so it could be called before each request
+    let generator = snowflake_jwt::JwtGenerator::new(&private_key, &full_identifier)?;
+    let jwt = generator.token()?;
 
     println!("{}", &args.sql);
 
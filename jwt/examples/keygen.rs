@@ -0,0 +1,59 @@
+use anyhow::Result;
+use clap::Parser;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+extern crate snowflake_jwt;
+
+/// Private key is encrypted with the passphrase from this variable when it's set,
+/// it's not taken as an argument, so it doesn't end up in the shell history or process list
+const PASSPHRASE_ENV: &str = "SNOWFLAKE_PRIVATE_KEY_PASSPHRASE";
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+#[command(after_help = "Set SNOWFLAKE_PRIVATE_KEY_PASSPHRASE to encrypt the private key")]
+struct Args {
+    /// Directory to write `rsa_key.p8` and `rsa_key.pub` to
+    #[arg(long, default_value = ".")]
+    out_dir: PathBuf,
+
+    /// username to whom the public key would be assigned
+    #[arg(short, long)]
+    username: String,
+}
+
+/// Private key is only readable by the owner, same as `ssh-keygen` does
+fn write_private_key(path: &Path, pem: &str) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // mode only applies to new files, so tighten permissions of the overwritten one too
+        if path.exists() {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+
+    options.open(path)?.write_all(pem.as_bytes())?;
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let key_pair = snowflake_jwt::KeyPair::generate()?;
+
+    let private_pem = match std::env::var(PASSPHRASE_ENV) {
+        Ok(passphrase) => key_pair.encrypted_private_key_pem(&passphrase)?,
+        Err(_) => key_pair.private_key_pem()?,
+    };
+    write_private_key(&args.out_dir.join("rsa_key.p8"), &private_pem)?;
+    fs::write(args.out_dir.join("rsa_key.pub"), key_pair.public_key_pem()?)?;
+
+    println!("Fingerprint: {}", key_pair.fingerprint()?);
+    println!("{}", key_pair.alter_user_statement(&args.username)?);
+
+    Ok(())
+}
@@ -0,0 +1,242 @@
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use time::OffsetDateTime;
+
+use crate::{Claims, JwtError};
+
+/// Decoded token claims, signature is not verified, so it should only be used for diagnostics
+#[derive(Debug, Clone)]
+pub struct TokenInfo {
+    /// `<ACCOUNT>.<USER>.SHA256:<fingerprint>`
+    pub issuer: String,
+    /// `<ACCOUNT>.<USER>`
+    pub subject: String,
+    pub issued_at: OffsetDateTime,
+    pub expires_at: OffsetDateTime,
+    /// Public key fingerprint taken from the issuer, `None` if issuer is malformed
+    pub fingerprint: Option<String>,
+}
+
+/// Problem with the token which would make Snowflake reject it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenIssue {
+    /// Subject has to be uppercase `<ACCOUNT>.<USER>`
+    SubjectMismatch {
+        expected: String,
+        actual: String,
+    },
+    /// Issuer has to be the subject followed by the public key fingerprint
+    IssuerMismatch {
+        expected: String,
+        actual: String,
+    },
+    /// Fingerprint doesn't match the one registered for the user
+    FingerprintMismatch {
+        expected: String,
+        actual: String,
+    },
+    /// Issue time is ahead of the local clock
+    IssuedInFuture(Duration),
+    Expired(Duration),
+}
+
+impl Display for TokenIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenIssue::SubjectMismatch { expected, actual } => {
+                write!(f, "subject is `{actual}`, expected `{expected}`")
+            }
+            TokenIssue::IssuerMismatch { expected, actual } => {
+                write!(
+                    f,
+                    "issuer is `{actual}`, expected it to start with `{expected}.SHA256:`"
+                )
+            }
+            TokenIssue::FingerprintMismatch { expected, actual } => write!(
+                f,
+                "public key fingerprint is `{actual}`, but `{expected}` is registered for the user"
+            ),
+            TokenIssue::IssuedInFuture(d) => write!(
+                f,
+                "token is issued {}s in the future, check the local clock",
+                d.as_secs()
+            ),
+            TokenIssue::Expired(d) => write!(f, "token has expired {}s ago", d.as_secs()),
+        }
+    }
+}
+
+impl Display for TokenInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "iss: {}, sub: {}, iat: {}, exp: {}, fingerprint: {}",
+            self.issuer,
+            self.subject,
+            self.issued_at,
+            self.expires_at,
+            self.fingerprint.as_deref().unwrap_or("<missing>")
+        )
+    }
+}
+
+impl TokenInfo {
+    /// Decode token without verifying its signature
+    pub fn decode(token: &str) -> Result<Self, JwtError> {
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.insecure_disable_signature_validation();
+        validation.validate_exp = false;
+        validation.validate_aud = false;
+        validation.required_spec_claims.clear();
+
+        let claims = decode::<Claims>(token, &DecodingKey::from_secret(&[]), &validation)?.claims;
+        let fingerprint = claims
+            .iss
+            .find(".SHA256:")
+            .map(|i| claims.iss[i + 1..].to_owned());
+
+        Ok(Self {
+            issuer: claims.iss,
+            subject: claims.sub,
+            issued_at: claims.iat,
+            expires_at: claims.exp,
+            fingerprint,
+        })
+    }
+
+    /// Compare claims against the account and user the token is meant for, and optionally
+    /// against the fingerprint registered for the user (`RSA_PUBLIC_KEY_FP` from `DESC USER`).
+    /// Empty result means that the token looks fine and the problem is elsewhere,
+    /// eg public key isn't assigned to the user.
+    pub fn diagnose(
+        &self,
+        account_identifier: &str,
+        username: &str,
+        registered_fingerprint: Option<&str>,
+    ) -> Vec<TokenIssue> {
+        let mut issues = vec![];
+
+        let expected = format!(
+            "{}.{}",
+            account_identifier.to_uppercase(),
+            username.to_uppercase()
+        );
+        if self.subject != expected {
+            issues.push(TokenIssue::SubjectMismatch {
+                expected: expected.clone(),
+                actual: self.subject.clone(),
+            });
+        }
+
+        let issuer_prefix = format!("{expected}.SHA256:");
+        if !self.issuer.starts_with(&issuer_prefix) {
+            issues.push(TokenIssue::IssuerMismatch {
+                expected,
+                actual: self.issuer.clone(),
+            });
+        }
+
+        if let (Some(registered), Some(actual)) = (registered_fingerprint, &self.fingerprint) {
+            if registered.trim() != actual {
+                issues.push(TokenIssue::FingerprintMismatch {
+                    expected: registered.trim().to_owned(),
+                    actual: actual.clone(),
+                });
+            }
+        }
+
+        let now = OffsetDateTime::now_utc();
+        if self.issued_at > now {
+            issues.push(TokenIssue::IssuedInFuture(
+                (self.issued_at - now).unsigned_abs(),
+            ));
+        }
+        if self.expires_at < now {
+            issues.push(TokenIssue::Expired((now - self.expires_at).unsigned_abs()));
+        }
+
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FINGERPRINT: &str = "SHA256:abc=";
+
+    fn info(issuer: &str, subject: &str, issued_ago: i64, expires_in: i64) -> TokenInfo {
+        let now = OffsetDateTime::now_utc();
+        TokenInfo {
+            issuer: issuer.to_owned(),
+            subject: subject.to_owned(),
+            issued_at: now - time::Duration::seconds(issued_ago),
+            expires_at: now + time::Duration::seconds(expires_in),
+            fingerprint: issuer.find(".SHA256:").map(|i| issuer[i + 1..].to_owned()),
+        }
+    }
+
+    #[test]
+    fn valid_token_has_no_issues() {
+        let info = info("ORG-ACCOUNT.USER.SHA256:abc=", "ORG-ACCOUNT.USER", 60, 3540);
+        assert_eq!(info.diagnose("org-account", "user", Some(FINGERPRINT)), []);
+        assert_eq!(info.diagnose("org-account", "user", None), []);
+    }
+
+    #[test]
+    fn identifier_issues() {
+        let info = info("org-account.user.SHA256:abc=", "org-account.user", 60, 3540);
+        assert_eq!(
+            info.diagnose("org-account", "user", None),
+            [
+                TokenIssue::SubjectMismatch {
+                    expected: "ORG-ACCOUNT.USER".to_owned(),
+                    actual: "org-account.user".to_owned(),
+                },
+                TokenIssue::IssuerMismatch {
+                    expected: "ORG-ACCOUNT.USER".to_owned(),
+                    actual: "org-account.user.SHA256:abc=".to_owned(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn fingerprint_mismatch() {
+        let info = info("ORG-ACCOUNT.USER.SHA256:abc=", "ORG-ACCOUNT.USER", 60, 3540);
+        assert_eq!(
+            info.diagnose("org-account", "user", Some(" SHA256:other= ")),
+            [TokenIssue::FingerprintMismatch {
+                expected: "SHA256:other=".to_owned(),
+                actual: FINGERPRINT.to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn clock_issues() {
+        let future = info(
+            "ORG-ACCOUNT.USER.SHA256:abc=",
+            "ORG-ACCOUNT.USER",
+            -600,
+            3600,
+        );
+        assert!(matches!(
+            future.diagnose("org-account", "user", None)[..],
+            [TokenIssue::IssuedInFuture(d)] if (599..=600).contains(&d.as_secs())
+        ));
+
+        let expired = info(
+            "ORG-ACCOUNT.USER.SHA256:abc=",
+            "ORG-ACCOUNT.USER",
+            7200,
+            -3600,
+        );
+        assert!(matches!(
+            expired.diagnose("org-account", "user", None)[..],
+            [TokenIssue::Expired(d)] if (3600..=3601).contains(&d.as_secs())
+        ));
+    }
+}
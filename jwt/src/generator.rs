@@ -0,0 +1,159 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use rsa::pkcs1::EncodeRsaPrivateKey;
+use rsa::RsaPrivateKey;
+use time::OffsetDateTime;
+
+use crate::{public_key_fingerprint, Claims, JwtError};
+
+/// Snowflake documents an hour as the maximum token lifetime, counted from the issue time
+pub const DEFAULT_LIFETIME: Duration = Duration::from_secs(60 * 60);
+/// Allowed difference between the local clock and the Snowflake one
+pub const DEFAULT_CLOCK_SKEW: Duration = Duration::from_secs(60);
+
+struct CachedToken {
+    token: String,
+    exp: OffsetDateTime,
+}
+
+/// Generates JWT tokens for the same key and user, parsed key and its fingerprint are kept,
+/// so tokens are cheap to generate. Generated token is cached and given out until it's about
+/// to expire, which makes it suitable for the `Authorization: Bearer` header in SQL REST API.
+pub struct JwtGenerator {
+    encoding_key: EncodingKey,
+    // Snowflake expects uppercase <account identifier>.<username>
+    full_identifier: String,
+    fingerprint: String,
+    lifetime: Duration,
+    clock_skew: Duration,
+    cached: Mutex<Option<CachedToken>>,
+}
+
+impl JwtGenerator {
+    pub fn new(private_key: &RsaPrivateKey, full_identifier: &str) -> Result<Self, JwtError> {
+        let fingerprint = public_key_fingerprint(private_key)?;
+        let encoding_key = EncodingKey::from_rsa_der(private_key.to_pkcs1_der()?.as_bytes());
+
+        Ok(Self {
+            encoding_key,
+            full_identifier: full_identifier.to_owned(),
+            fingerprint,
+            lifetime: DEFAULT_LIFETIME,
+            clock_skew: DEFAULT_CLOCK_SKEW,
+            cached: Mutex::new(None),
+        })
+    }
+
+    /// How long generated tokens are valid for, counting from the back-dated issue time
+    #[must_use]
+    pub fn with_lifetime(mut self, lifetime: Duration) -> Self {
+        self.lifetime = lifetime;
+        self
+    }
+
+    /// Issue time is moved back by this amount and cached tokens are renewed this much earlier,
+    /// so tokens are accepted even if the local clock is off
+    #[must_use]
+    pub fn with_clock_skew(mut self, clock_skew: Duration) -> Self {
+        self.clock_skew = clock_skew;
+        self
+    }
+
+    /// Public key fingerprint in `SHA256:<base64>` format, as shown by `DESC USER`
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    /// Get cached token, new one is generated if it's about to expire
+    pub fn token(&self) -> Result<String, JwtError> {
+        self.token_at(OffsetDateTime::now_utc())
+    }
+
+    fn token_at(&self, now: OffsetDateTime) -> Result<String, JwtError> {
+        let mut cached = self.cached.lock().unwrap();
+        if let Some(c) = cached.as_ref().filter(|c| now + self.clock_skew < c.exp) {
+            return Ok(c.token.clone());
+        }
+
+        let (token, exp) = self.generate_at(now)?;
+        *cached = Some(CachedToken {
+            token: token.clone(),
+            exp,
+        });
+
+        Ok(token)
+    }
+
+    /// Always generate a new token, bypassing the cache
+    pub fn generate(&self) -> Result<String, JwtError> {
+        self.generate_at(OffsetDateTime::now_utc())
+            .map(|(token, _)| token)
+    }
+
+    fn generate_at(&self, now: OffsetDateTime) -> Result<(String, OffsetDateTime), JwtError> {
+        let iss = format!("{}.{}", self.full_identifier, self.fingerprint);
+        let iat = now - self.clock_skew;
+        // lifetime is counted from `iat`, otherwise the skew would push it over the maximum
+        let exp = iat + self.lifetime;
+
+        let claims = Claims::new(iss, self.full_identifier.clone(), iat, exp);
+        let token = encode(&Header::new(Algorithm::RS256), &claims, &self.encoding_key)?;
+
+        Ok((token, claims.exp))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::TokenInfo;
+    use crate::keypair::test_key;
+
+    fn generator() -> JwtGenerator {
+        JwtGenerator::new(test_key().private_key(), "ACCOUNT.USER").unwrap()
+    }
+
+    fn at(unix: i64) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(unix).unwrap()
+    }
+
+    #[test]
+    fn default_token_is_valid_for_an_hour() {
+        let token = generator().token_at(at(1_700_000_000)).unwrap();
+        let info = TokenInfo::decode(&token).unwrap();
+
+        assert_eq!(info.issued_at, at(1_700_000_000 - 60));
+        assert_eq!(info.expires_at - info.issued_at, DEFAULT_LIFETIME);
+    }
+
+    #[test]
+    fn token_is_cached_until_it_is_about_to_expire() {
+        let generator = generator();
+        let first = generator.token_at(at(1_700_000_000)).unwrap();
+        // expires at 1_700_003_540, renewed within the clock skew of that
+        let renew_at = 1_700_003_540 - 60;
+
+        assert_eq!(generator.token_at(at(1_700_000_001)).unwrap(), first);
+        assert_eq!(generator.token_at(at(renew_at - 1)).unwrap(), first);
+
+        let renewed = generator.token_at(at(renew_at)).unwrap();
+        assert_ne!(renewed, first);
+        assert_eq!(
+            TokenInfo::decode(&renewed).unwrap().issued_at,
+            at(renew_at - 60)
+        );
+        assert_eq!(generator.token_at(at(renew_at + 1)).unwrap(), renewed);
+    }
+
+    #[test]
+    fn generate_bypasses_cache() {
+        let generator = generator().with_clock_skew(Duration::ZERO);
+        let cached = generator.token_at(at(1_700_000_000)).unwrap();
+
+        let info = TokenInfo::decode(&generator.generate().unwrap()).unwrap();
+        assert_ne!(info.issued_at, at(1_700_000_000));
+        assert_eq!(generator.token_at(at(1_700_000_001)).unwrap(), cached);
+    }
+}
@@ -0,0 +1,204 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use url::Url;
+
+use crate::SnowflakeApiError;
+
+const DEFAULT_DOMAIN: &str = "snowflakecomputing.com";
+const DOMAINS: &[&str] = &[DEFAULT_DOMAIN, "snowflakecomputing.cn"];
+
+/// Account identifier in any of the formats accepted by Snowflake:
+/// `org-account`, `org_account`, legacy `locator.region.cloud` or a full account url.
+/// See <https://docs.snowflake.com/en/user-guide/admin-account-identifier>
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountIdentifier {
+    account_name: String,
+    host: String,
+}
+
+impl AccountIdentifier {
+    pub fn parse(account_identifier: &str) -> Result<Self, SnowflakeApiError> {
+        let invalid = || SnowflakeApiError::InvalidAccountIdentifier(account_identifier.to_owned());
+
+        let trimmed = account_identifier.trim();
+        let host = if trimmed.contains("://") {
+            let url = Url::parse(trimmed).map_err(|_| invalid())?;
+            url.host_str().ok_or_else(invalid)?.to_owned()
+        } else {
+            trimmed.trim_end_matches('/').to_owned()
+        }
+        .to_lowercase();
+
+        let (identifier, domain) = DOMAINS
+            .iter()
+            .find_map(|domain| {
+                host.strip_suffix(domain)
+                    .and_then(|i| i.strip_suffix('.'))
+                    .map(|i| (i, *domain))
+            })
+            .unwrap_or((host.as_str(), DEFAULT_DOMAIN));
+
+        // region and cloud are only a part of the hostname, eg `xy12345.us-east-2.aws`
+        let account_name = if identifier.ends_with(".global") {
+            // legacy global urls are `<account>-<suffix>.global`
+            identifier.split(['-', '.']).next()
+        } else {
+            identifier.split('.').next()
+        }
+        .unwrap_or_default()
+        .to_uppercase();
+
+        let is_valid = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+        if account_name.is_empty() || !account_name.chars().all(is_valid) {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            account_name,
+            // underscores are not allowed in hostnames
+            host: format!("{}.{domain}", identifier.replace('_', "-")),
+        })
+    }
+
+    /// Uppercase account name without region, used as `ACCOUNT_NAME` on login
+    /// and in the JWT issuer and subject
+    pub fn account_name(&self) -> &str {
+        &self.account_name
+    }
+
+    /// Hostname of the account, eg `org-account.snowflakecomputing.com`
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+}
+
+impl FromStr for AccountIdentifier {
+    type Err = SnowflakeApiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl Display for AccountIdentifier {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.account_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_account_identifier() {
+        let cases = [
+            (
+                "org-account",
+                "ORG-ACCOUNT",
+                "org-account.snowflakecomputing.com",
+            ),
+            (
+                "ORG-Account",
+                "ORG-ACCOUNT",
+                "org-account.snowflakecomputing.com",
+            ),
+            (
+                "org_account",
+                "ORG_ACCOUNT",
+                "org-account.snowflakecomputing.com",
+            ),
+            (
+                " org-account/ ",
+                "ORG-ACCOUNT",
+                "org-account.snowflakecomputing.com",
+            ),
+            ("xy12345", "XY12345", "xy12345.snowflakecomputing.com"),
+            (
+                "xy12345.us-east-2.aws",
+                "XY12345",
+                "xy12345.us-east-2.aws.snowflakecomputing.com",
+            ),
+            (
+                "xy12345.us-east-2.aws.snowflakecomputing.com",
+                "XY12345",
+                "xy12345.us-east-2.aws.snowflakecomputing.com",
+            ),
+            (
+                "https://org-account.snowflakecomputing.com",
+                "ORG-ACCOUNT",
+                "org-account.snowflakecomputing.com",
+            ),
+            (
+                "https://org_account.snowflakecomputing.com:443/console",
+                "ORG_ACCOUNT",
+                "org-account.snowflakecomputing.com",
+            ),
+            (
+                "xy12345.cn-northwest-1.aws.snowflakecomputing.cn",
+                "XY12345",
+                "xy12345.cn-northwest-1.aws.snowflakecomputing.cn",
+            ),
+            (
+                "https://org-account.snowflakecomputing.cn",
+                "ORG-ACCOUNT",
+                "org-account.snowflakecomputing.cn",
+            ),
+            (
+                "org-account.privatelink",
+                "ORG-ACCOUNT",
+                "org-account.privatelink.snowflakecomputing.com",
+            ),
+            (
+                "xy12345.us-east-1.privatelink.snowflakecomputing.com",
+                "XY12345",
+                "xy12345.us-east-1.privatelink.snowflakecomputing.com",
+            ),
+            (
+                "xy12345-abcdef.global",
+                "XY12345",
+                "xy12345-abcdef.global.snowflakecomputing.com",
+            ),
+            (
+                "https://xy12345-abcdef.global.snowflakecomputing.com",
+                "XY12345",
+                "xy12345-abcdef.global.snowflakecomputing.com",
+            ),
+        ];
+
+        for (input, account_name, host) in cases {
+            let account = AccountIdentifier::parse(input).unwrap();
+            assert_eq!(account.account_name(), account_name, "{input}");
+            assert_eq!(account.host(), host, "{input}");
+        }
+    }
+
+    #[test]
+    fn invalid_account_identifier() {
+        for input in [
+            "",
+            "  ",
+            "/",
+            "https://",
+            "org account",
+            "org!account",
+            ".snowflakecomputing.com",
+            "https://:443",
+        ] {
+            assert!(
+                matches!(
+                    AccountIdentifier::parse(input),
+                    Err(SnowflakeApiError::InvalidAccountIdentifier(i)) if i == input
+                ),
+                "{input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn from_str_and_display() {
+        let account: AccountIdentifier = "org-account".parse().unwrap();
+        assert_eq!(account.to_string(), "ORG-ACCOUNT");
+    }
+}
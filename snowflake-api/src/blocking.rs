@@ -0,0 +1,182 @@
+//! Synchronous wrapper around [`SnowflakeApi`], for programs which don't use async otherwise.
+//! Each client owns a single-threaded tokio runtime, so it can't be used from within
+//! an async context, calls made there fail with
+//! [`SnowflakeApiError::BlockingInAsyncContext`].
+
+use std::future::Future;
+use std::sync::Arc;
+
+use tokio::runtime::Runtime;
+
+use crate::{
+    AuthArgs, QueryResult, RawQueryResult, SessionState, SnowflakeApi, SnowflakeApiBuilder,
+    SnowflakeApiError,
+};
+
+/// Blocking Snowflake API, clones share the session and the runtime
+#[derive(Clone)]
+pub struct BlockingSnowflakeApi {
+    api: SnowflakeApi,
+    runtime: Arc<OwnedRuntime>,
+}
+
+/// Runtime which could be dropped within async context, unlike the tokio one
+struct OwnedRuntime(Option<Runtime>);
+
+impl Drop for OwnedRuntime {
+    fn drop(&mut self) {
+        if let Some(runtime) = self.0.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+impl BlockingSnowflakeApi {
+    pub fn new(api: SnowflakeApi) -> Result<Self, SnowflakeApiError> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+
+        Ok(Self {
+            api,
+            runtime: Arc::new(OwnedRuntime(Some(runtime))),
+        })
+    }
+
+    /// Nested `block_on` would panic, so it's reported as an error instead
+    fn block_on<T>(
+        &self,
+        future: impl Future<Output = Result<T, SnowflakeApiError>>,
+    ) -> Result<T, SnowflakeApiError> {
+        if tokio::runtime::Handle::try_current().is_ok() {
+            return Err(SnowflakeApiError::BlockingInAsyncContext);
+        }
+        let runtime = self
+            .runtime
+            .0
+            .as_ref()
+            .expect("runtime is only taken on drop");
+        runtime.block_on(future)
+    }
+
+    /// Initialize object with password auth. Authentication happens on the first request.
+    pub fn with_password_auth(
+        account_identifier: &str,
+        warehouse: Option<&str>,
+        database: Option<&str>,
+        schema: Option<&str>,
+        username: &str,
+        role: Option<&str>,
+        password: &str,
+    ) -> Result<Self, SnowflakeApiError> {
+        Self::new(SnowflakeApi::with_password_auth(
+            account_identifier,
+            warehouse,
+            database,
+            schema,
+            username,
+            role,
+            password,
+        )?)
+    }
+
+    /// Initialize object with private certificate auth. Authentication happens on the first request.
+    pub fn with_certificate_auth(
+        account_identifier: &str,
+        warehouse: Option<&str>,
+        database: Option<&str>,
+        schema: Option<&str>,
+        username: &str,
+        role: Option<&str>,
+        private_key_pem: &str,
+    ) -> Result<Self, SnowflakeApiError> {
+        Self::new(SnowflakeApi::with_certificate_auth(
+            account_identifier,
+            warehouse,
+            database,
+            schema,
+            username,
+            role,
+            private_key_pem,
+        )?)
+    }
+
+    pub fn from_env() -> Result<Self, SnowflakeApiError> {
+        Self::new(SnowflakeApiBuilder::new(AuthArgs::from_env()?).build()?)
+    }
+
+    /// See [`SnowflakeApi::session_state`]
+    pub fn session_state(&self) -> Result<Option<SessionState>, SnowflakeApiError> {
+        self.block_on(async { Ok(self.api.session_state().await) })
+    }
+
+    /// See [`SnowflakeApi::close_session`]
+    pub fn close_session(&self) -> Result<(), SnowflakeApiError> {
+        self.block_on(self.api.close_session())
+    }
+
+    /// Execute a single query against API.
+    /// If statement is PUT, then file will be uploaded to the Snowflake-managed storage
+    pub fn exec(&self, sql: &str) -> Result<QueryResult, SnowflakeApiError> {
+        self.block_on(self.api.exec(sql))
+    }
+
+    /// Same as [`BlockingSnowflakeApi::exec`], returns raw bytes in the Arrow response
+    pub fn exec_raw(&self, sql: &str) -> Result<RawQueryResult, SnowflakeApiError> {
+        self.block_on(self.api.exec_raw(sql))
+    }
+
+    /// Underlying async API
+    pub fn inner(&self) -> &SnowflakeApi {
+        &self.api
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{login_response, query_response, Request, Response, TestServer};
+
+    fn snowflake(req: &Request) -> Response {
+        match req.path.as_str() {
+            "/session/v1/login-request" => Response::json(&login_response(1)),
+            "/queries/v1/query-request" => Response::json(&query_response("value")),
+            _ => Response::not_found(),
+        }
+    }
+
+    #[test]
+    fn exec() {
+        // server needs its own runtime, blocking client can't be used within one
+        let server_runtime = Runtime::new().unwrap();
+        let server = server_runtime.block_on(TestServer::start(snowflake));
+        let api = BlockingSnowflakeApi::new(server.api_builder().build().unwrap()).unwrap();
+
+        let QueryResult::Json(res) = api.exec("SELECT 'value'").unwrap() else {
+            panic!("expected JSON result");
+        };
+
+        assert_eq!(res.value, serde_json::json!([["value"]]));
+        assert!(api.session_state().unwrap().is_some());
+        assert_eq!(
+            server.paths(),
+            ["/session/v1/login-request", "/queries/v1/query-request"]
+        );
+    }
+
+    #[tokio::test]
+    async fn exec_within_runtime() {
+        let server = TestServer::start(snowflake).await;
+        let api = BlockingSnowflakeApi::new(server.api_builder().build().unwrap()).unwrap();
+
+        let res = api.exec("SELECT 'value'");
+
+        assert!(matches!(
+            res,
+            Err(SnowflakeApiError::BlockingInAsyncContext)
+        ));
+        assert!(server.requests().is_empty());
+        // dropping the client's runtime within async context doesn't panic either
+        drop(api);
+    }
+}
@@ -0,0 +1,552 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+use url::Url;
+
+use crate::connection::Endpoint;
+use crate::{
+    AuthArgs, AuthType, CertificateArgs, ExternalBrowserArgs, MfaArgs, OAuthArgs, OktaArgs,
+    PasswordArgs, PatArgs, PrivateKey,
+};
+
+/// Keys which could be overridden with `SNOWFLAKE_CONNECTIONS_<NAME>_<KEY>` or `SNOWFLAKE_<KEY>`
+const KEYS: &[&str] = &[
+    "account",
+    "user",
+    "password",
+    "authenticator",
+    "private_key_file",
+    "private_key_path",
+    "private_key_file_pwd",
+    "private_key_raw",
+    "token",
+    "token_file_path",
+    "passcode",
+    "passcode_in_password",
+    "role",
+    "warehouse",
+    "database",
+    "schema",
+    "host",
+    "port",
+    "protocol",
+];
+
+#[derive(Error, Debug)]
+pub enum ConnectionConfigError {
+    #[error("Failed to read `{}`", .0.display())]
+    Io(PathBuf, #[source] std::io::Error),
+
+    #[error("Failed to parse `{}`", .0.display())]
+    Parse(PathBuf, #[source] toml::de::Error),
+
+    #[error("Snowflake config directory wasn't found, set `SNOWFLAKE_HOME`")]
+    MissingHome,
+
+    #[error("Connection `{0}` is not defined")]
+    UnknownConnection(String),
+
+    #[error("Connection `{0}` is missing required `{1}`")]
+    MissingField(String, String),
+
+    #[error("Connection `{0}` has invalid `{1}`")]
+    InvalidField(String, String),
+
+    #[error("Authenticator `{0}` is not supported")]
+    UnsupportedAuthenticator(String),
+
+    #[error("Invalid connection string: {0}")]
+    InvalidDsn(String),
+}
+
+/// Connections shared with Snowflake CLI and drivers, defined in `connections.toml`
+/// or in the `[connections]` section of `config.toml`, see
+/// <https://docs.snowflake.com/en/developer-guide/snowflake-cli/connecting/configure-connections>
+pub struct ConnectionsFile {
+    default_connection_name: Option<String>,
+    connections: HashMap<String, HashMap<String, String>>,
+}
+
+impl ConnectionsFile {
+    /// Load both files from `SNOWFLAKE_HOME`, `~/.snowflake` or the OS config directory,
+    /// connections from `connections.toml` take precedence over the ones from `config.toml`
+    pub fn load() -> Result<Self, ConnectionConfigError> {
+        let home = snowflake_home().ok_or(ConnectionConfigError::MissingHome)?;
+        let mut file = Self {
+            default_connection_name: None,
+            connections: HashMap::new(),
+        };
+
+        if let Some(config) = read_toml(&home.join("config.toml"))? {
+            file.default_connection_name = config
+                .get("default_connection_name")
+                .and_then(toml::Value::as_str)
+                .map(str::to_owned);
+            if let Some(toml::Value::Table(connections)) = config.get("connections") {
+                file.extend(connections);
+            }
+        }
+
+        if let Some(connections) = read_toml(&home.join("connections.toml"))? {
+            file.extend(&connections);
+        }
+
+        Ok(file)
+    }
+
+    /// `SNOWFLAKE_DEFAULT_CONNECTION_NAME`, then `default_connection_name` from `config.toml`,
+    /// otherwise `default`
+    pub fn default_connection_name(&self) -> String {
+        std::env::var("SNOWFLAKE_DEFAULT_CONNECTION_NAME")
+            .ok()
+            .or_else(|| self.default_connection_name.clone())
+            .unwrap_or_else(|| "default".to_owned())
+    }
+
+    /// Resolve connection into auth arguments, applying env overrides
+    pub fn auth_args(&self, name: &str) -> Result<AuthArgs, ConnectionConfigError> {
+        self.params(name)?.auth_args()
+    }
+
+    /// Same as [`ConnectionsFile::auth_args`], together with `host`, `port` and `protocol`
+    pub fn connection(&self, name: &str) -> Result<(AuthArgs, Endpoint), ConnectionConfigError> {
+        self.params(name)?.into_parts()
+    }
+
+    fn params<'a>(&self, name: &'a str) -> Result<ConnectionParams<'a>, ConnectionConfigError> {
+        let mut params = self
+            .connections
+            .get(name)
+            .cloned()
+            .ok_or_else(|| ConnectionConfigError::UnknownConnection(name.to_owned()))?;
+
+        for key in KEYS {
+            let specific = format!(
+                "SNOWFLAKE_CONNECTIONS_{}_{}",
+                name.to_uppercase(),
+                key.to_uppercase()
+            );
+            let generic = format!("SNOWFLAKE_{}", key.to_uppercase());
+            if let Ok(value) = std::env::var(specific).or_else(|_| std::env::var(generic)) {
+                params.insert((*key).to_owned(), value);
+            }
+        }
+
+        Ok(ConnectionParams { name, params })
+    }
+
+    fn extend(&mut self, connections: &toml::Table) {
+        for (name, params) in connections {
+            if let toml::Value::Table(params) = params {
+                let params = params
+                    .iter()
+                    .map(|(k, v)| {
+                        let v = match v {
+                            toml::Value::String(s) => s.clone(),
+                            v => v.to_string(),
+                        };
+                        (k.clone(), v)
+                    })
+                    .collect();
+                self.connections.insert(name.clone(), params);
+            }
+        }
+    }
+}
+
+/// Connection parameters named the same way as in `connections.toml`, shared with the DSN parser
+pub struct ConnectionParams<'a> {
+    pub name: &'a str,
+    pub params: HashMap<String, String>,
+}
+
+impl ConnectionParams<'_> {
+    pub fn into_parts(mut self) -> Result<(AuthArgs, Endpoint), ConnectionConfigError> {
+        let endpoint = Endpoint {
+            host: self.params.remove("host"),
+            port: match self.params.remove("port") {
+                Some(port) => Some(port.parse().map_err(|_| self.invalid("port"))?),
+                None => None,
+            },
+            protocol: self.params.remove("protocol"),
+        };
+
+        Ok((self.auth_args()?, endpoint))
+    }
+
+    pub fn auth_args(mut self) -> Result<AuthArgs, ConnectionConfigError> {
+        let auth_type = self.auth_type()?;
+
+        Ok(AuthArgs {
+            account_identifier: self.required("account")?,
+            warehouse: self.params.remove("warehouse"),
+            database: self.params.remove("database"),
+            schema: self.params.remove("schema"),
+            username: self.required("user")?,
+            role: self.params.remove("role"),
+            auth_type,
+        })
+    }
+
+    fn auth_type(&mut self) -> Result<AuthType, ConnectionConfigError> {
+        let has_key = ["private_key_file", "private_key_path", "private_key_raw"]
+            .iter()
+            .any(|k| self.params.contains_key(*k));
+        let authenticator = match self.params.remove("authenticator") {
+            Some(authenticator) => authenticator,
+            None if has_key => "snowflake_jwt".to_owned(),
+            None => "snowflake".to_owned(),
+        };
+
+        match authenticator.to_lowercase().as_str() {
+            "snowflake" => Ok(AuthType::Password(PasswordArgs {
+                password: self.required("password")?.into(),
+            })),
+            "snowflake_jwt" => Ok(AuthType::Certificate(CertificateArgs {
+                private_key: self.private_key()?,
+                private_key_passphrase: self.params.remove("private_key_file_pwd").map(Into::into),
+                secondary_private_key: None,
+                secondary_private_key_passphrase: None,
+            })),
+            "oauth" => Ok(AuthType::OAuth(OAuthArgs {
+                token: self.token()?,
+                refresher: None,
+            })),
+            "programmatic_access_token" => Ok(AuthType::Pat(PatArgs {
+                token: self.token()?.into(),
+            })),
+            "externalbrowser" => Ok(AuthType::ExternalBrowser(ExternalBrowserArgs::default())),
+            "username_password_mfa" => Ok(AuthType::Mfa(MfaArgs {
+                password: self.required("password")?.into(),
+                passcode: self.params.remove("passcode"),
+                passcode_in_password: self.flag("passcode_in_password")?,
+                cache: None,
+            })),
+            okta if okta.starts_with("https://") => Ok(AuthType::Okta(OktaArgs {
+                authenticator: Url::parse(&authenticator)
+                    .map_err(|_| self.invalid("authenticator"))?,
+                password: self.required("password")?.into(),
+            })),
+            _ => Err(ConnectionConfigError::UnsupportedAuthenticator(
+                authenticator,
+            )),
+        }
+    }
+
+    fn private_key(&mut self) -> Result<PrivateKey, ConnectionConfigError> {
+        if let Some(pem) = self.params.remove("private_key_raw") {
+            return Ok(PrivateKey::Pem(pem));
+        }
+
+        let path = match self.params.remove("private_key_file") {
+            Some(path) => path,
+            None => self.required("private_key_path")?,
+        };
+        Ok(PrivateKey::File(expand_home(&path)))
+    }
+
+    fn token(&mut self) -> Result<String, ConnectionConfigError> {
+        if let Some(token) = self.params.remove("token") {
+            return Ok(token);
+        }
+
+        let path = expand_home(&self.required("token_file_path")?);
+        let token =
+            std::fs::read_to_string(&path).map_err(|e| ConnectionConfigError::Io(path, e))?;
+        Ok(token.trim().to_owned())
+    }
+
+    fn flag(&mut self, key: &str) -> Result<bool, ConnectionConfigError> {
+        match self.params.remove(key).as_deref() {
+            None => Ok(false),
+            Some(v) => v.to_lowercase().parse().map_err(|_| self.invalid(key)),
+        }
+    }
+
+    fn required(&mut self, key: &str) -> Result<String, ConnectionConfigError> {
+        self.params.remove(key).ok_or_else(|| {
+            ConnectionConfigError::MissingField(self.name.to_owned(), key.to_owned())
+        })
+    }
+
+    fn invalid(&self, key: &str) -> ConnectionConfigError {
+        ConnectionConfigError::InvalidField(self.name.to_owned(), key.to_owned())
+    }
+}
+
+/// Same lookup order as in Snowflake CLI
+fn snowflake_home() -> Option<PathBuf> {
+    if let Ok(home) = std::env::var("SNOWFLAKE_HOME") {
+        return Some(expand_home(&home));
+    }
+
+    let dot_snowflake = dirs::home_dir().map(|home| home.join(".snowflake"));
+    match dot_snowflake {
+        Some(path) if path.is_dir() => Some(path),
+        _ => dirs::config_dir().map(|config| config.join("snowflake")),
+    }
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Missing files are skipped
+fn read_toml(path: &Path) -> Result<Option<toml::Table>, ConnectionConfigError> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(ConnectionConfigError::Io(path.to_owned(), e)),
+    };
+
+    content
+        .parse()
+        .map(Some)
+        .map_err(|e| ConnectionConfigError::Parse(path.to_owned(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::TestEnv;
+
+    /// `SNOWFLAKE_HOME` pointing to a temporary directory with the given files,
+    /// other `SNOWFLAKE_*` variables are cleared
+    struct TestHome {
+        path: PathBuf,
+        _env: TestEnv,
+    }
+
+    impl TestHome {
+        fn new(files: &[(&str, &str)]) -> Self {
+            let env = TestEnv::new();
+            let path =
+                std::env::temp_dir().join(format!("snowflake-home-{}", uuid::Uuid::new_v4()));
+            std::fs::create_dir(&path).unwrap();
+            for (name, content) in files {
+                std::fs::write(path.join(name), content).unwrap();
+            }
+            std::env::set_var("SNOWFLAKE_HOME", &path);
+
+            Self { path, _env: env }
+        }
+    }
+
+    impl Drop for TestHome {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.path);
+        }
+    }
+
+    const CONFIG: &str = r#"
+default_connection_name = "dev"
+
+[connections.dev]
+account = "org-config"
+user = "config_user"
+password = "config_password"
+warehouse = "CONFIG_WH"
+
+[connections.shared]
+account = "org-shared"
+user = "shared_user"
+password = "shared_password"
+"#;
+
+    const CONNECTIONS: &str = r#"
+[dev]
+account = "org-dev"
+user = "dev_user"
+password = "dev_password"
+port = 8443
+
+[prod]
+account = "org-prod"
+user = "prod_user"
+private_key_file = "/keys/rsa_key.p8"
+"#;
+
+    fn password(args: &AuthArgs) -> String {
+        match &args.auth_type {
+            AuthType::Password(PasswordArgs {
+                password: crate::Credential::Plain(password),
+            }) => password.clone(),
+            _ => panic!("expected plain password auth"),
+        }
+    }
+
+    #[test]
+    fn connections_file_overrides_config() {
+        let _home = TestHome::new(&[("config.toml", CONFIG), ("connections.toml", CONNECTIONS)]);
+        let file = ConnectionsFile::load().unwrap();
+
+        // whole connection is replaced, not merged key by key
+        let (dev, endpoint) = file.connection("dev").unwrap();
+        assert_eq!(dev.account_identifier, "org-dev");
+        assert_eq!(dev.username, "dev_user");
+        assert_eq!(password(&dev), "dev_password");
+        assert_eq!(dev.warehouse, None);
+        assert_eq!(endpoint.port, Some(8443));
+        assert_eq!(endpoint.host, None);
+
+        let shared = file.auth_args("shared").unwrap();
+        assert_eq!(shared.account_identifier, "org-shared");
+        assert!(file.auth_args("prod").is_ok());
+        assert!(matches!(
+            file.auth_args("missing"),
+            Err(ConnectionConfigError::UnknownConnection(name)) if name == "missing"
+        ));
+    }
+
+    #[test]
+    fn missing_files_are_skipped() {
+        let _home = TestHome::new(&[("connections.toml", CONNECTIONS)]);
+        let file = ConnectionsFile::load().unwrap();
+        assert_eq!(file.auth_args("dev").unwrap().username, "dev_user");
+        assert_eq!(file.default_connection_name(), "default");
+    }
+
+    #[test]
+    fn invalid_file_is_reported() {
+        let _home = TestHome::new(&[("config.toml", "[connections")]);
+        assert!(matches!(
+            ConnectionsFile::load(),
+            Err(ConnectionConfigError::Parse(path, _)) if path.ends_with("config.toml")
+        ));
+    }
+
+    #[test]
+    fn env_overrides() {
+        let _home = TestHome::new(&[("connections.toml", CONNECTIONS)]);
+        std::env::set_var("SNOWFLAKE_CONNECTIONS_DEV_PASSWORD", "specific_password");
+        std::env::set_var("SNOWFLAKE_PASSWORD", "generic_password");
+        std::env::set_var("SNOWFLAKE_ROLE", "GENERIC_ROLE");
+        std::env::set_var("SNOWFLAKE_CONNECTIONS_PROD_ACCOUNT", "org-other");
+        let file = ConnectionsFile::load().unwrap();
+
+        let dev = file.auth_args("dev").unwrap();
+        assert_eq!(password(&dev), "specific_password");
+        assert_eq!(dev.role.as_deref(), Some("GENERIC_ROLE"));
+        // override of the other connection doesn't apply
+        assert_eq!(dev.account_identifier, "org-dev");
+
+        let prod = file.auth_args("prod").unwrap();
+        assert_eq!(prod.account_identifier, "org-other");
+    }
+
+    #[test]
+    fn default_connection_name_precedence() {
+        let home = TestHome::new(&[]);
+        assert_eq!(
+            ConnectionsFile::load().unwrap().default_connection_name(),
+            "default"
+        );
+
+        std::fs::write(home.path.join("config.toml"), CONFIG).unwrap();
+        let file = ConnectionsFile::load().unwrap();
+        assert_eq!(file.default_connection_name(), "dev");
+
+        std::env::set_var("SNOWFLAKE_DEFAULT_CONNECTION_NAME", "prod");
+        assert_eq!(file.default_connection_name(), "prod");
+    }
+
+    fn auth_type(params: &[(&str, &str)]) -> Result<AuthType, ConnectionConfigError> {
+        let mut params: HashMap<String, String> = params
+            .iter()
+            .map(|(k, v)| ((*k).to_owned(), (*v).to_owned()))
+            .collect();
+        params.insert("account".to_owned(), "org-account".to_owned());
+        params.insert("user".to_owned(), "user".to_owned());
+
+        ConnectionParams {
+            name: "test",
+            params,
+        }
+        .auth_args()
+        .map(|args| args.auth_type)
+    }
+
+    #[test]
+    fn authenticator_inference() {
+        assert!(matches!(
+            auth_type(&[("password", "p")]),
+            Ok(AuthType::Password(_))
+        ));
+        assert!(matches!(
+            auth_type(&[("private_key_file", "/keys/rsa_key.p8")]),
+            Ok(AuthType::Certificate(CertificateArgs {
+                private_key: PrivateKey::File(path),
+                ..
+            })) if path == Path::new("/keys/rsa_key.p8")
+        ));
+        assert!(matches!(
+            auth_type(&[("private_key_raw", "pem")]),
+            Ok(AuthType::Certificate(CertificateArgs {
+                private_key: PrivateKey::Pem(_),
+                ..
+            }))
+        ));
+        assert!(matches!(
+            auth_type(&[("authenticator", "OAUTH"), ("token", "t")]),
+            Ok(AuthType::OAuth(OAuthArgs { token, .. })) if token == "t"
+        ));
+        assert!(matches!(
+            auth_type(&[
+                ("authenticator", "programmatic_access_token"),
+                ("token", "t")
+            ]),
+            Ok(AuthType::Pat(_))
+        ));
+        assert!(matches!(
+            auth_type(&[("authenticator", "externalbrowser")]),
+            Ok(AuthType::ExternalBrowser(_))
+        ));
+        assert!(matches!(
+            auth_type(&[
+                ("authenticator", "username_password_mfa"),
+                ("password", "p"),
+                ("passcode_in_password", "True")
+            ]),
+            Ok(AuthType::Mfa(MfaArgs {
+                passcode_in_password: true,
+                ..
+            }))
+        ));
+        assert!(matches!(
+            auth_type(&[("authenticator", "https://org.okta.com"), ("password", "p")]),
+            Ok(AuthType::Okta(OktaArgs { authenticator, .. })) if authenticator.as_str() == "https://org.okta.com/"
+        ));
+    }
+
+    #[test]
+    fn invalid_authenticator_config() {
+        assert!(matches!(
+            auth_type(&[]),
+            Err(ConnectionConfigError::MissingField(_, key)) if key == "password"
+        ));
+        assert!(matches!(
+            auth_type(&[("authenticator", "snowflake_jwt")]),
+            Err(ConnectionConfigError::MissingField(_, key)) if key == "private_key_path"
+        ));
+        assert!(matches!(
+            auth_type(&[("authenticator", "oauth")]),
+            Err(ConnectionConfigError::MissingField(_, key)) if key == "token_file_path"
+        ));
+        assert!(matches!(
+            auth_type(&[("authenticator", "saml")]),
+            Err(ConnectionConfigError::UnsupportedAuthenticator(a)) if a == "saml"
+        ));
+        assert!(matches!(
+            auth_type(&[
+                ("authenticator", "username_password_mfa"),
+                ("password", "p"),
+                ("passcode_in_password", "maybe")
+            ]),
+            Err(ConnectionConfigError::InvalidField(_, key)) if key == "passcode_in_password"
+        ));
+    }
+}
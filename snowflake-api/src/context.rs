@@ -0,0 +1,308 @@
+use std::fmt::{Display, Formatter};
+
+use crate::responses::{QueryExecResponseData, SessionInfo};
+
+/// Role, warehouse, database and schema in use by the session. Seeded from the login
+/// response and updated after every query, so `USE` statements are reflected too.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionContext {
+    pub role: Option<String>,
+    pub warehouse: Option<String>,
+    pub database: Option<String>,
+    pub schema: Option<String>,
+}
+
+impl From<&SessionInfo> for SessionContext {
+    fn from(info: &SessionInfo) -> Self {
+        Self {
+            role: Some(info.role_name.clone()),
+            warehouse: info.warehouse_name.clone(),
+            database: info.database_name.clone(),
+            schema: info.schema_name.clone(),
+        }
+    }
+}
+
+impl From<&QueryExecResponseData> for SessionContext {
+    fn from(data: &QueryExecResponseData) -> Self {
+        Self {
+            role: Some(data.final_role_name.clone()),
+            warehouse: data.final_warehouse_name.clone(),
+            database: data.final_database_name.clone(),
+            schema: data.final_schema_name.clone(),
+        }
+    }
+}
+
+/// Argument of `USE SECONDARY ROLES`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecondaryRoles {
+    /// All roles granted to the user
+    All,
+    None,
+    /// Empty list is the same as [`SecondaryRoles::None`]
+    Roles(Vec<String>),
+}
+
+impl Display for SecondaryRoles {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SecondaryRoles::All => f.write_str("ALL"),
+            SecondaryRoles::None => f.write_str("NONE"),
+            SecondaryRoles::Roles(roles) if roles.is_empty() => f.write_str("NONE"),
+            SecondaryRoles::Roles(roles) => f.write_str(&roles.join(", ")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::test_server::{
+        close_response, login_response, query_response, Request, Response, TestServer,
+    };
+    use crate::SnowflakeApi;
+
+    const LOGIN_PATH: &str = "/session/v1/login-request";
+    const QUERY_PATH: &str = "/queries/v1/query-request";
+
+    /// Tracks the context like Snowflake does: requested on login, defaults are used
+    /// for the rest, `USE` statements change it and `CALL set_role()` does so behind the scenes
+    fn snowflake() -> impl Fn(&Request) -> Response {
+        let context = Mutex::new(SessionContext::default());
+        move |req| {
+            let mut context = context.lock().unwrap();
+            match req.path.as_str() {
+                LOGIN_PATH => {
+                    let param = |name: &str, default: &str| {
+                        Some(login_param(req, name).unwrap_or_else(|| default.to_owned()))
+                    };
+                    *context = SessionContext {
+                        role: param("roleName", "PUBLIC"),
+                        warehouse: param("warehouse", "LOGIN_WH"),
+                        database: param("databaseName", "LOGIN_DB"),
+                        schema: param("schemaName", "LOGIN_SCHEMA"),
+                    };
+                    let mut resp = login_response(1);
+                    resp["data"]["sessionInfo"] = serde_json::json!({
+                        "roleName": context.role,
+                        "warehouseName": context.warehouse,
+                        "databaseName": context.database,
+                        "schemaName": context.schema,
+                    });
+                    Response::json(&resp)
+                }
+                QUERY_PATH => {
+                    let sql = req.json()["sqlText"].as_str().unwrap().to_uppercase();
+                    let words: Vec<_> = sql.split_whitespace().collect();
+                    match words.as_slice() {
+                        ["USE", "ROLE", role] => context.role = Some((*role).to_owned()),
+                        ["USE", "WAREHOUSE", wh] => context.warehouse = Some((*wh).to_owned()),
+                        ["USE", "DATABASE", db] => context.database = Some((*db).to_owned()),
+                        ["USE", "SCHEMA", schema] => match schema.split_once('.') {
+                            Some((db, schema)) => {
+                                context.database = Some(db.to_owned());
+                                context.schema = Some(schema.to_owned());
+                            }
+                            None => context.schema = Some((*schema).to_owned()),
+                        },
+                        ["CALL", "SET_ROLE()"] => context.role = Some("PROC_ROLE".to_owned()),
+                        _ => {}
+                    }
+                    let mut resp = query_response("ok");
+                    resp["data"]["finalRoleName"] = context.role.clone().into();
+                    resp["data"]["finalWarehouseName"] = context.warehouse.clone().into();
+                    resp["data"]["finalDatabaseName"] = context.database.clone().into();
+                    resp["data"]["finalSchemaName"] = context.schema.clone().into();
+                    Response::json(&resp)
+                }
+                "/session" => Response::json(&close_response()),
+                _ => Response::not_found(),
+            }
+        }
+    }
+
+    fn login_param(req: &Request, name: &str) -> Option<String> {
+        url::form_urlencoded::parse(req.query.as_bytes())
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.into_owned())
+    }
+
+    fn context(role: &str, warehouse: &str, database: &str, schema: &str) -> SessionContext {
+        SessionContext {
+            role: Some(role.to_owned()),
+            warehouse: Some(warehouse.to_owned()),
+            database: Some(database.to_owned()),
+            schema: Some(schema.to_owned()),
+        }
+    }
+
+    /// API requesting `analyst` role and `builder_wh` warehouse on login
+    fn api(server: &TestServer) -> SnowflakeApi {
+        let mut builder = server.api_builder();
+        builder.auth.role = Some("analyst".to_owned());
+        builder.auth.warehouse = Some("builder_wh".to_owned());
+        builder.build().unwrap()
+    }
+
+    /// Statements sent to the query endpoint
+    fn statements(server: &TestServer) -> Vec<String> {
+        server
+            .requests()
+            .iter()
+            .filter(|r| r.path == QUERY_PATH)
+            .map(|r| r.json()["sqlText"].as_str().unwrap().to_owned())
+            .collect()
+    }
+
+    fn logins(server: &TestServer) -> Vec<Request> {
+        server
+            .requests()
+            .into_iter()
+            .filter(|r| r.path == LOGIN_PATH)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn context_is_seeded_from_login_response() {
+        let server = TestServer::start(snowflake()).await;
+        let api = api(&server);
+
+        assert_eq!(
+            api.current_context(),
+            SessionContext {
+                role: Some("ANALYST".to_owned()),
+                warehouse: Some("BUILDER_WH".to_owned()),
+                database: None,
+                schema: None,
+            }
+        );
+
+        api.session.get_token().await.unwrap();
+
+        assert_eq!(server.paths(), [LOGIN_PATH]);
+        assert_eq!(
+            api.current_context(),
+            context("ANALYST", "BUILDER_WH", "LOGIN_DB", "LOGIN_SCHEMA")
+        );
+    }
+
+    #[tokio::test]
+    async fn context_is_updated_from_query_response() {
+        let server = TestServer::start(snowflake()).await;
+        let api = api(&server);
+
+        api.exec_raw("CALL set_role()").await.unwrap();
+
+        assert_eq!(statements(&server), ["CALL set_role()"]);
+        assert_eq!(
+            api.current_context(),
+            context("PROC_ROLE", "BUILDER_WH", "LOGIN_DB", "LOGIN_SCHEMA")
+        );
+    }
+
+    #[tokio::test]
+    async fn use_statements_change_context() {
+        let server = TestServer::start(snowflake()).await;
+        let api = api(&server);
+
+        api.use_role("loader").await.unwrap();
+        assert_eq!(
+            api.current_context(),
+            context("LOADER", "BUILDER_WH", "LOGIN_DB", "LOGIN_SCHEMA")
+        );
+        api.use_warehouse("load_wh").await.unwrap();
+        assert_eq!(
+            api.current_context(),
+            context("LOADER", "LOAD_WH", "LOGIN_DB", "LOGIN_SCHEMA")
+        );
+        api.use_database("raw").await.unwrap();
+        assert_eq!(
+            api.current_context(),
+            context("LOADER", "LOAD_WH", "RAW", "LOGIN_SCHEMA")
+        );
+        api.use_schema("staging.landing").await.unwrap();
+        assert_eq!(
+            api.current_context(),
+            context("LOADER", "LOAD_WH", "STAGING", "LANDING")
+        );
+        api.use_secondary_roles(&SecondaryRoles::All).await.unwrap();
+        assert_eq!(
+            api.current_context(),
+            context("LOADER", "LOAD_WH", "STAGING", "LANDING")
+        );
+
+        assert_eq!(
+            statements(&server),
+            [
+                "USE ROLE loader",
+                "USE WAREHOUSE load_wh",
+                "USE DATABASE raw",
+                "USE SCHEMA staging.landing",
+                "USE SECONDARY ROLES ALL",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn relogin_requests_tracked_context() {
+        let server = TestServer::start(snowflake()).await;
+        let api = api(&server);
+        api.use_role("loader").await.unwrap();
+        api.use_warehouse("load_wh").await.unwrap();
+
+        // new session is started by the next statement
+        api.session.close().await.unwrap();
+        api.exec_raw("SELECT 1").await.unwrap();
+
+        let logins = logins(&server);
+        assert_eq!(logins.len(), 2);
+        assert_eq!(
+            login_param(&logins[0], "roleName").as_deref(),
+            Some("ANALYST")
+        );
+        assert_eq!(
+            login_param(&logins[0], "warehouse").as_deref(),
+            Some("BUILDER_WH")
+        );
+        assert_eq!(login_param(&logins[0], "databaseName"), None);
+        assert_eq!(login_param(&logins[0], "schemaName"), None);
+        assert_eq!(
+            login_param(&logins[1], "roleName").as_deref(),
+            Some("LOADER")
+        );
+        assert_eq!(
+            login_param(&logins[1], "warehouse").as_deref(),
+            Some("LOAD_WH")
+        );
+        assert_eq!(
+            login_param(&logins[1], "databaseName").as_deref(),
+            Some("LOGIN_DB")
+        );
+        assert_eq!(
+            login_param(&logins[1], "schemaName").as_deref(),
+            Some("LOGIN_SCHEMA")
+        );
+        assert_eq!(
+            statements(&server),
+            ["USE ROLE loader", "USE WAREHOUSE load_wh", "SELECT 1"]
+        );
+        assert_eq!(
+            api.current_context(),
+            context("LOADER", "LOAD_WH", "LOGIN_DB", "LOGIN_SCHEMA")
+        );
+    }
+
+    #[test]
+    fn secondary_roles() {
+        let roles = |r: &[&str]| SecondaryRoles::Roles(r.iter().map(|r| (*r).to_owned()).collect());
+
+        assert_eq!(SecondaryRoles::All.to_string(), "ALL");
+        assert_eq!(SecondaryRoles::None.to_string(), "NONE");
+        assert_eq!(roles(&[]).to_string(), "NONE");
+        assert_eq!(roles(&["ANALYST"]).to_string(), "ANALYST");
+        assert_eq!(roles(&["ANALYST", "LOADER"]).to_string(), "ANALYST, LOADER");
+    }
+}
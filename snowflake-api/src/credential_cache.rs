@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+
+/// Kind of the secure token, which is issued by Snowflake on login
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CredentialKind {
+    /// Allows to skip MFA prompt for repeated logins within the allowed window
+    MfaToken,
+}
+
+/// Tokens are cached per account and user
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CredentialKey {
+    pub account: String,
+    pub user: String,
+    pub kind: CredentialKind,
+}
+
+/// Storage for the tokens which allow to skip interactive steps on login, eg MFA prompt.
+/// Implement it to persist tokens between processes, eg in OS keychain.
+#[async_trait]
+pub trait CredentialCache: Send + Sync {
+    async fn get(&self, key: &CredentialKey) -> Option<String>;
+
+    async fn set(&self, key: &CredentialKey, token: &str);
+
+    async fn remove(&self, key: &CredentialKey);
+}
+
+/// Keeps tokens for the lifetime of the process, used by default
+#[derive(Default)]
+pub struct InMemoryCredentialCache {
+    tokens: Mutex<HashMap<CredentialKey, String>>,
+}
+
+#[async_trait]
+impl CredentialCache for InMemoryCredentialCache {
+    async fn get(&self, key: &CredentialKey) -> Option<String> {
+        self.tokens.lock().unwrap().get(key).cloned()
+    }
+
+    async fn set(&self, key: &CredentialKey, token: &str) {
+        self.tokens
+            .lock()
+            .unwrap()
+            .insert(key.clone(), token.to_owned());
+    }
+
+    async fn remove(&self, key: &CredentialKey) {
+        self.tokens.lock().unwrap().remove(key);
+    }
+}
@@ -0,0 +1,201 @@
+use std::error::Error;
+use std::fmt::{Debug, Formatter};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+/// Supplies the secret (password, private key PEM, passphrase) on demand. It's asked on every
+/// login, so rotated secrets are picked up without restarting, implement it to integrate with
+/// Vault or a cloud secret manager.
+#[async_trait]
+pub trait CredentialProvider: Send + Sync {
+    async fn secret(&self) -> Result<String, Box<dyn Error + Send + Sync>>;
+}
+
+/// Secret which is either known upfront or fetched from the provider on every login
+#[derive(Clone)]
+pub enum Credential {
+    Plain(String),
+    Provider(Arc<dyn CredentialProvider>),
+}
+
+impl Credential {
+    pub async fn resolve(&self) -> Result<String, Box<dyn Error + Send + Sync>> {
+        match self {
+            Credential::Plain(secret) => Ok(secret.clone()),
+            Credential::Provider(provider) => provider.secret().await,
+        }
+    }
+}
+
+impl Debug for Credential {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Credential::Plain(_) => f.write_str("Plain(<redacted>)"),
+            Credential::Provider(_) => f.write_str("Provider"),
+        }
+    }
+}
+
+impl From<String> for Credential {
+    fn from(secret: String) -> Self {
+        Credential::Plain(secret)
+    }
+}
+
+impl From<&str> for Credential {
+    fn from(secret: &str) -> Self {
+        Credential::Plain(secret.to_owned())
+    }
+}
+
+impl From<Arc<dyn CredentialProvider>> for Credential {
+    fn from(provider: Arc<dyn CredentialProvider>) -> Self {
+        Credential::Provider(provider)
+    }
+}
+
+/// Reads secret from the environment variable
+pub struct EnvCredentialProvider {
+    name: String,
+}
+
+impl EnvCredentialProvider {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+        }
+    }
+}
+
+#[async_trait]
+impl CredentialProvider for EnvCredentialProvider {
+    async fn secret(&self) -> Result<String, Box<dyn Error + Send + Sync>> {
+        std::env::var(&self.name)
+            .map_err(|_| format!("Environment variable `{}` is not set", self.name).into())
+    }
+}
+
+/// Reads secret from the file, trailing newline is stripped
+pub struct FileCredentialProvider {
+    path: PathBuf,
+}
+
+impl FileCredentialProvider {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[async_trait]
+impl CredentialProvider for FileCredentialProvider {
+    async fn secret(&self) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let secret = tokio::fs::read_to_string(&self.path).await?;
+        Ok(secret.trim_end_matches(['\r', '\n']).to_owned())
+    }
+}
+
+/// Runs the command and takes secret from its stdout, eg `vault kv get -field=password secret/sf`.
+/// Command is executed directly, without shell.
+pub struct CommandCredentialProvider {
+    program: String,
+    args: Vec<String>,
+}
+
+impl CommandCredentialProvider {
+    pub fn new(program: &str) -> Self {
+        Self {
+            program: program.to_owned(),
+            args: vec![],
+        }
+    }
+
+    #[must_use]
+    pub fn arg(mut self, arg: &str) -> Self {
+        self.args.push(arg.to_owned());
+        self
+    }
+}
+
+#[async_trait]
+impl CredentialProvider for CommandCredentialProvider {
+    async fn secret(&self) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let output = tokio::process::Command::new(&self.program)
+            .args(&self.args)
+            .kill_on_drop(true)
+            .output()
+            .await?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "Command `{}` failed with {}: {}",
+                self.program,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+            .into());
+        }
+
+        let secret = String::from_utf8(output.stdout)?;
+        Ok(secret.trim_end_matches(['\r', '\n']).to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::TestEnv;
+
+    #[tokio::test]
+    async fn env_provider() {
+        let _env = TestEnv::new();
+        let provider = EnvCredentialProvider::new("SNOWFLAKE_TEST_SECRET");
+
+        let err = provider.secret().await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Environment variable `SNOWFLAKE_TEST_SECRET` is not set"
+        );
+
+        std::env::set_var("SNOWFLAKE_TEST_SECRET", "secret\n");
+        // environment is taken as is, unlike files and command output
+        assert_eq!(provider.secret().await.unwrap(), "secret\n");
+    }
+
+    #[tokio::test]
+    async fn file_provider() {
+        let path = std::env::temp_dir().join(format!("snowflake-secret-{}", uuid::Uuid::new_v4()));
+        let provider = FileCredentialProvider::new(&path);
+
+        assert!(provider.secret().await.is_err());
+
+        std::fs::write(&path, "  secret \r\n\n").unwrap();
+        let secret = provider.secret().await;
+        // secret is read on every call, so the rotated one is picked up
+        std::fs::write(&path, "rotated").unwrap();
+        let rotated = provider.secret().await;
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(secret.unwrap(), "  secret ");
+        assert_eq!(rotated.unwrap(), "rotated");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn command_provider() {
+        let provider = CommandCredentialProvider::new("echo").arg("secret");
+        assert_eq!(provider.secret().await.unwrap(), "secret");
+
+        let provider = CommandCredentialProvider::new("false");
+        let err = provider.secret().await.unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("Command `false` failed with exit status: 1"),
+            "{err}"
+        );
+
+        let provider = CommandCredentialProvider::new("snowflake-rs-missing-command");
+        assert!(provider.secret().await.is_err());
+    }
+}
@@ -0,0 +1,251 @@
+use std::process::Command;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use url::Url;
+
+use crate::session::AuthError;
+
+const SUCCESS_PAGE: &str = "<!DOCTYPE html><html><head><meta charset=\"UTF-8\"/>\
+<title>SAML Response for Snowflake</title></head><body>\
+Your identity was confirmed and propagated to Snowflake. \
+You can close this window now and go back where you started from.\
+</body></html>";
+
+/// Max size of the request browser is expected to send to the callback listener
+const MAX_REQUEST_SIZE: usize = 16 * 1024;
+/// Browsers open connections ahead of time, which are left idle
+const CALLBACK_READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Shows SSO url to the user, [`open_browser`] is used by default
+pub type BrowserOpener = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// Localhost listener, which receives SAML token when identity provider
+/// redirects browser back after successful authentication
+pub struct CallbackListener {
+    listener: TcpListener,
+}
+
+impl CallbackListener {
+    /// Bind to a random free port on the loopback interface
+    pub async fn bind() -> Result<Self, AuthError> {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        Ok(Self { listener })
+    }
+
+    pub fn port(&self) -> Result<u16, AuthError> {
+        Ok(self.listener.local_addr()?.port())
+    }
+
+    /// Wait for the browser to be redirected with the token. Connections are served
+    /// concurrently, so idle preconnects and other requests (eg favicon) are answered
+    /// or dropped without holding up the redirect, and their failures are only logged.
+    pub async fn wait_for_token(&self, timeout: Duration) -> Result<String, AuthError> {
+        let (token_tx, mut token_rx) = mpsc::channel(1);
+
+        tokio::time::timeout(timeout, async {
+            loop {
+                tokio::select! {
+                    accepted = self.listener.accept() => match accepted {
+                        Ok((stream, _)) => {
+                            let token_tx = token_tx.clone();
+                            tokio::spawn(async move {
+                                match Self::serve(stream).await {
+                                    Ok(Some(token)) => {
+                                        let _ = token_tx.send(token).await;
+                                    }
+                                    Ok(None) => {}
+                                    Err(e) => log::debug!("Ignoring failed browser callback: {e}"),
+                                }
+                            });
+                        }
+                        Err(e) => log::debug!("Failed to accept browser callback: {e}"),
+                    },
+                    Some(token) = token_rx.recv() => return token,
+                }
+            }
+        })
+        .await
+        .map_err(|_| AuthError::BrowserAuthTimeout)
+    }
+
+    /// Answer the request, returning the token if it's the redirect
+    async fn serve(mut stream: TcpStream) -> Result<Option<String>, AuthError> {
+        let token = tokio::time::timeout(CALLBACK_READ_TIMEOUT, Self::read_token(&mut stream))
+            .await
+            .map_err(|_| {
+                AuthError::BrowserAuthFailed("timed out reading callback request".to_owned())
+            })??;
+
+        if token.is_some() {
+            Self::respond(&mut stream, "200 OK", SUCCESS_PAGE).await?;
+        } else {
+            Self::respond(&mut stream, "404 Not Found", "").await?;
+        }
+        Ok(token)
+    }
+
+    async fn read_token(stream: &mut TcpStream) -> Result<Option<String>, AuthError> {
+        let mut buf = Vec::new();
+        let mut chunk = [0u8; 1024];
+        // only request line and headers are of interest, token is passed as a query parameter
+        while !buf.windows(4).any(|w| w == b"\r\n\r\n") && buf.len() < MAX_REQUEST_SIZE {
+            let n = stream.read(&mut chunk).await?;
+            if n == 0 {
+                break;
+            }
+            buf.extend_from_slice(&chunk[..n]);
+        }
+
+        let request = String::from_utf8_lossy(&buf);
+        let target = request
+            .lines()
+            .next()
+            .and_then(|line| line.strip_prefix("GET "))
+            .and_then(|line| line.split_whitespace().next());
+
+        let Some(target) = target else {
+            return Ok(None);
+        };
+
+        // request target is relative, base is only needed for parsing
+        let url = Url::parse("http://localhost")
+            .and_then(|base| base.join(target))
+            .map_err(|_| AuthError::BrowserAuthFailed(format!("malformed callback `{target}`")))?;
+        let token = url
+            .query_pairs()
+            .find(|(k, _)| k == "token")
+            .map(|(_, v)| v.into_owned());
+
+        Ok(token)
+    }
+
+    async fn respond(stream: &mut TcpStream, status: &str, body: &str) -> Result<(), AuthError> {
+        let resp = format!(
+            "HTTP/1.1 {status}\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        stream.write_all(resp.as_bytes()).await?;
+        stream.shutdown().await?;
+        Ok(())
+    }
+}
+
+/// Open SSO url in the default browser, url is always logged as well.
+/// When browser can't be opened, eg over ssh, url is logged as a warning,
+/// use custom [`BrowserOpener`] to show it otherwise
+pub fn open_browser(url: &str) {
+    log::info!(
+        "Initiating login request with your identity provider. \
+        A browser window should have opened for you to complete the login. \
+        If you can't see it, go to the following url: {url}"
+    );
+
+    let res = if cfg!(target_os = "macos") {
+        Command::new("open").arg(url).spawn()
+    } else if cfg!(target_os = "windows") {
+        Command::new("rundll32")
+            .args(["url.dll,FileProtocolHandler", url])
+            .spawn()
+    } else {
+        Command::new("xdg-open").arg(url).spawn()
+    };
+
+    match res {
+        Ok(mut child) => {
+            // opener exits with an error when there is no browser or display to use
+            let url = url.to_owned();
+            std::thread::spawn(move || match child.wait() {
+                Ok(status) if status.success() => {}
+                Ok(status) => browser_unavailable(&url, &status.to_string()),
+                Err(e) => browser_unavailable(&url, &e.to_string()),
+            });
+        }
+        Err(e) => browser_unavailable(url, &e.to_string()),
+    }
+}
+
+fn browser_unavailable(url: &str, reason: &str) {
+    log::warn!(
+        "Failed to open the browser: {reason}. \
+        Go to the following url to complete the login: {url}"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Send a request to the listener and return the response status line
+    async fn get(port: u16, target: &str) -> String {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let request = format!("GET {target} HTTP/1.1\r\nHost: localhost:{port}\r\n\r\n");
+        stream.write_all(request.as_bytes()).await.unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response.lines().next().unwrap_or_default().to_owned()
+    }
+
+    #[tokio::test]
+    async fn token_is_received_from_redirect() {
+        let listener = CallbackListener::bind().await.unwrap();
+        let port = listener.port().unwrap();
+
+        let browser = tokio::spawn(async move {
+            let favicon = get(port, "/favicon.ico").await;
+            let redirect = get(port, "/?token=abc%2Bdef%3D&confirm=true").await;
+            (favicon, redirect)
+        });
+        let token = listener
+            .wait_for_token(Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        assert_eq!(token, "abc+def=");
+        let (favicon, redirect) = browser.await.unwrap();
+        assert_eq!(favicon, "HTTP/1.1 404 Not Found");
+        assert_eq!(redirect, "HTTP/1.1 200 OK");
+    }
+
+    #[tokio::test]
+    async fn stray_connections_dont_block_redirect() {
+        let listener = CallbackListener::bind().await.unwrap();
+        let port = listener.port().unwrap();
+
+        let browser = tokio::spawn(async move {
+            // idle preconnect is kept open, while the other one is closed right away
+            let idle = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+            drop(TcpStream::connect(("127.0.0.1", port)).await.unwrap());
+            let redirect = get(port, "/?token=abc").await;
+            drop(idle);
+            redirect
+        });
+        let token = listener
+            .wait_for_token(Duration::from_secs(1))
+            .await
+            .unwrap();
+
+        assert_eq!(token, "abc");
+        assert_eq!(browser.await.unwrap(), "HTTP/1.1 200 OK");
+    }
+
+    #[tokio::test]
+    async fn wait_for_token_times_out() {
+        let listener = CallbackListener::bind().await.unwrap();
+        let port = listener.port().unwrap();
+
+        // requests without the token don't complete the login
+        let browser = tokio::spawn(async move { get(port, "/favicon.ico").await });
+        let err = listener
+            .wait_for_token(Duration::from_millis(200))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, AuthError::BrowserAuthTimeout), "{err:?}");
+        assert_eq!(browser.await.unwrap(), "HTTP/1.1 404 Not Found");
+    }
+}
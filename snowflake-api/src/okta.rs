@@ -0,0 +1,238 @@
+use regex::Regex;
+use url::Url;
+
+use crate::account::AccountIdentifier;
+use crate::connection::Connection;
+use crate::credential_provider::Credential;
+use crate::requests::OktaTokenRequest;
+use crate::responses::{AuthenticatorResponseData, OktaTokenResponse};
+use crate::session::AuthError;
+
+/// Credentials for the native Okta authenticator, username is kept as-is
+/// since Okta is not necessarily case-insensitive about it
+pub struct OktaCredentials {
+    /// Okta organization url, eg `https://<org>.okta.com`
+    pub authenticator: Url,
+    pub username: String,
+    pub password: Credential,
+}
+
+/// Authenticate against Okta and fetch SAML response, which could be exchanged for
+/// Snowflake session. Identity provider urls come from the authenticator request.
+pub async fn saml_response(
+    connection: &Connection,
+    credentials: &OktaCredentials,
+    idp: &AuthenticatorResponseData,
+    account_identifier: &AccountIdentifier,
+) -> Result<String, AuthError> {
+    let token_url = idp
+        .token_url
+        .as_deref()
+        .ok_or(AuthError::UnexpectedResponse)?;
+    let token_url = parse_idp_url(token_url)?;
+    let mut sso_url = parse_idp_url(&idp.sso_url)?;
+
+    // do not send credentials anywhere except the configured identity provider
+    for url in [&token_url, &sso_url] {
+        if !same_origin(url, &credentials.authenticator) {
+            return Err(AuthError::IdpUrlMismatch(url.to_string()));
+        }
+    }
+
+    let body = OktaTokenRequest {
+        username: credentials.username.clone(),
+        password: credentials
+            .password
+            .resolve()
+            .await
+            .map_err(AuthError::CredentialProviderFailed)?,
+    };
+    let resp: OktaTokenResponse = connection.post_external(token_url, body).await?;
+    let one_time_token = resp
+        .session_token
+        .or(resp.cookie_token)
+        .ok_or_else(|| AuthError::OktaAuthFailed("no session token in the response".to_owned()))?;
+
+    sso_url
+        .query_pairs_mut()
+        .append_pair("RelayState", "/some/deep/link")
+        .append_pair("onetimetoken", &one_time_token);
+    let html = connection.get_external_text(sso_url).await?;
+
+    // make sure SAML response is posted back to the account we are logging into
+    let postback_url = postback_url(&html)?;
+    let account_url = parse_idp_url(&connection.base_url(account_identifier))?;
+    if !same_origin(&postback_url, &account_url) {
+        return Err(AuthError::SamlPostbackMismatch(postback_url.to_string()));
+    }
+
+    Ok(html)
+}
+
+fn parse_idp_url(url: &str) -> Result<Url, AuthError> {
+    Url::parse(url).map_err(|_| AuthError::IdpUrlMismatch(url.to_owned()))
+}
+
+fn same_origin(a: &Url, b: &Url) -> bool {
+    a.scheme() == b.scheme()
+        && a.host_str().map(str::to_lowercase) == b.host_str().map(str::to_lowercase)
+        && a.port_or_known_default() == b.port_or_known_default()
+}
+
+/// SAML response is an html page with the auto-submitted form, form action is the postback url
+fn postback_url(html: &str) -> Result<Url, AuthError> {
+    let form_re = Regex::new(r#"(?is)<form[^>]*\saction="([^"]+)""#).unwrap();
+    let action = form_re
+        .captures(html)
+        .and_then(|c| c.get(1))
+        .ok_or_else(|| AuthError::OktaAuthFailed("no postback url in SAML response".to_owned()))?;
+    let action = unescape_html(action.as_str());
+
+    Url::parse(&action).map_err(|_| AuthError::SamlPostbackMismatch(action))
+}
+
+/// Okta escapes form action, eg `https&#x3a;&#x2f;&#x2f;`
+fn unescape_html(s: &str) -> String {
+    let entity_re = Regex::new(r"&(#[xX][0-9a-fA-F]+|#[0-9]+|amp|lt|gt|quot|apos);").unwrap();
+    entity_re
+        .replace_all(s, |c: &regex::Captures| {
+            let entity = &c[1];
+            let decoded = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                _ if entity.starts_with("#x") || entity.starts_with("#X") => {
+                    u32::from_str_radix(&entity[2..], 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                }
+                _ => entity[1..].parse().ok().and_then(char::from_u32),
+            };
+            decoded.map_or_else(|| c[0].to_owned(), String::from)
+        })
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::context::SessionContext;
+    use crate::session::{Session, SessionConfig};
+    use crate::test_server::{login_response, Request, Response, TestServer};
+
+    const TOKEN_PATH: &str = "/api/v1/authn";
+    const SSO_PATH: &str = "/app/snowflake/exk/sso/saml";
+
+    /// Okta and Snowflake are both served locally, `idp` and `postback` override the urls
+    /// returned to the client
+    fn handler(
+        idp: Option<&'static str>,
+        postback: Option<&'static str>,
+    ) -> impl Fn(&Request) -> Response + Send + Sync {
+        move |req| {
+            let local = format!("http://{}", req.host);
+            match req.path.as_str() {
+                "/session/authenticator-request" => {
+                    let idp = idp.map_or_else(|| local.clone(), str::to_owned);
+                    Response::json(&json!({
+                        "data": {
+                            "tokenUrl": format!("{idp}{TOKEN_PATH}"),
+                            "ssoUrl": format!("{idp}{SSO_PATH}"),
+                            "proofKey": null
+                        },
+                        "code": null,
+                        "message": null,
+                        "success": true
+                    }))
+                }
+                TOKEN_PATH => Response::json(&json!({"sessionToken": "one-time-token"})),
+                SSO_PATH => {
+                    let action = postback
+                        .map_or_else(|| format!("{local}/fed/login"), str::to_owned)
+                        .replace(':', "&#x3a;")
+                        .replace('/', "&#x2f;");
+                    Response::html(format!(
+                        r#"<html><body><form method="post" action="{action}">
+                        <input type="hidden" name="SAMLResponse" value="saml"/></form></body></html>"#
+                    ))
+                }
+                "/session/v1/login-request" => Response::json(&login_response(1)),
+                _ => Response::not_found(),
+            }
+        }
+    }
+
+    async fn login(server: &TestServer) -> Result<(), AuthError> {
+        let config = SessionConfig {
+            connection: Arc::new(server.connection()),
+            account_identifier: AccountIdentifier::parse("org-account").unwrap(),
+            username: "Okta.User@example.com".to_owned(),
+            context: SessionContext::default(),
+        };
+        let authenticator = Url::parse(&server.url()).unwrap();
+        let session = Session::okta_auth(config, authenticator, "password".into());
+        session.get_token().await.map(|_| ())
+    }
+
+    #[tokio::test]
+    async fn okta_login() {
+        let server = TestServer::start(handler(None, None)).await;
+        login(&server).await.unwrap();
+
+        assert_eq!(
+            server.paths(),
+            [
+                "/session/authenticator-request",
+                TOKEN_PATH,
+                SSO_PATH,
+                "/session/v1/login-request"
+            ]
+        );
+        let requests = server.requests();
+        assert_eq!(
+            requests[1].json(),
+            json!({"username": "Okta.User@example.com", "password": "password"})
+        );
+        assert_eq!(requests[2].method, "GET");
+        assert!(requests[2].query.contains("onetimetoken=one-time-token"));
+        let login = requests[3].json();
+        assert!(login["data"]["RAW_SAML_RESPONSE"]
+            .as_str()
+            .unwrap()
+            .contains("SAMLResponse"));
+    }
+
+    #[tokio::test]
+    async fn credentials_are_not_sent_to_other_idp() {
+        let server = TestServer::start(handler(Some("https://evil.example.com"), None)).await;
+        let err = login(&server).await.unwrap_err();
+
+        assert!(
+            matches!(&err, AuthError::IdpUrlMismatch(url) if url.starts_with("https://evil.example.com/")),
+            "{err:?}"
+        );
+        assert_eq!(server.paths(), ["/session/authenticator-request"]);
+    }
+
+    #[tokio::test]
+    async fn saml_response_is_not_posted_to_other_account() {
+        let server =
+            TestServer::start(handler(None, Some("https://evil.example.com/fed/login"))).await;
+        let err = login(&server).await.unwrap_err();
+
+        assert!(
+            matches!(&err, AuthError::SamlPostbackMismatch(url) if url == "https://evil.example.com/fed/login"),
+            "{err:?}"
+        );
+        assert_eq!(
+            server.paths(),
+            ["/session/authenticator-request", TOKEN_PATH, SSO_PATH]
+        );
+    }
+}
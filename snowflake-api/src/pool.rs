@@ -0,0 +1,487 @@
+use std::collections::VecDeque;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::connection::Connection;
+use crate::credential_cache::InMemoryCredentialCache;
+use crate::{
+    AccountIdentifier, AuthArgs, AuthType, SnowflakeApi, SnowflakeApiBuilder, SnowflakeApiError,
+};
+
+const DEFAULT_MIN_SIZE: usize = 1;
+const DEFAULT_MAX_SIZE: usize = 10;
+const DEFAULT_ACQUIRE_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+const DEFAULT_HEALTH_CHECK_AFTER: Duration = Duration::from_secs(60);
+const HEALTH_CHECK_QUERY: &str = "SELECT 1";
+const MIN_REAP_INTERVAL: Duration = Duration::from_millis(100);
+
+#[must_use]
+pub struct SnowflakePoolBuilder {
+    api: SnowflakeApiBuilder,
+    min_size: usize,
+    max_size: usize,
+    acquire_timeout: Duration,
+    idle_timeout: Option<Duration>,
+    health_check_after: Option<Duration>,
+}
+
+impl SnowflakePoolBuilder {
+    /// Sessions are created from the same auth arguments and share the HTTP client,
+    /// session state set on the builder is ignored
+    pub fn new(api: SnowflakeApiBuilder) -> Self {
+        Self {
+            api,
+            min_size: DEFAULT_MIN_SIZE,
+            max_size: DEFAULT_MAX_SIZE,
+            acquire_timeout: DEFAULT_ACQUIRE_TIMEOUT,
+            idle_timeout: Some(DEFAULT_IDLE_TIMEOUT),
+            health_check_after: Some(DEFAULT_HEALTH_CHECK_AFTER),
+        }
+    }
+
+    /// Sessions which are kept even if idle, they log in lazily on the first query
+    pub fn with_min_size(mut self, min_size: usize) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// How long [`SnowflakePool::get`] waits for a session when all of them are in use
+    pub fn with_acquire_timeout(mut self, timeout: Duration) -> Self {
+        self.acquire_timeout = timeout;
+        self
+    }
+
+    /// Sessions idle for longer are closed, `None` keeps them forever. Idle sessions
+    /// are checked in the background if the pool is built within tokio runtime,
+    /// otherwise only when sessions are leased or returned.
+    pub fn with_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Sessions idle for longer are checked with `SELECT 1` before being handed out,
+    /// `None` disables the check
+    pub fn with_health_check_after(mut self, after: Option<Duration>) -> Self {
+        self.health_check_after = after;
+        self
+    }
+
+    pub fn build(mut self) -> Result<SnowflakePool, SnowflakeApiError> {
+        let connection = self.api.connection()?;
+        let account_identifier = AccountIdentifier::parse(&self.api.auth.account_identifier)?;
+
+        let mut auth = self.api.auth;
+        // otherwise every session would ask for its own MFA approval
+        if let AuthType::Mfa(args) = &mut auth.auth_type {
+            args.cache
+                .get_or_insert_with(|| Arc::new(InMemoryCredentialCache::default()));
+        }
+
+        let max_size = self.max_size.max(1);
+        let pool = SnowflakePool {
+            inner: Arc::new(PoolInner {
+                auth,
+                connection,
+                account_identifier,
+                state: Mutex::new(PoolState {
+                    idle: VecDeque::new(),
+                    size: 0,
+                }),
+                semaphore: Arc::new(Semaphore::new(max_size)),
+                min_size: self.min_size.min(max_size),
+                max_size,
+                acquire_timeout: self.acquire_timeout,
+                idle_timeout: self.idle_timeout,
+                health_check_after: self.health_check_after,
+            }),
+        };
+
+        for _ in 0..pool.inner.min_size {
+            let api = pool.inner.new_session();
+            pool.inner.release(api);
+        }
+        if let Some(idle_timeout) = pool.inner.idle_timeout {
+            spawn_reaper(&pool.inner, idle_timeout);
+        }
+
+        Ok(pool)
+    }
+}
+
+/// Pool of Snowflake sessions for running queries concurrently. Each session executes
+/// one query at a time, so a lease from [`SnowflakePool::get`] is exclusive until dropped.
+#[derive(Clone)]
+pub struct SnowflakePool {
+    inner: Arc<PoolInner>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStatus {
+    /// Both idle and leased sessions
+    pub size: usize,
+    pub idle: usize,
+    pub max_size: usize,
+}
+
+impl SnowflakePool {
+    pub fn builder(api: SnowflakeApiBuilder) -> SnowflakePoolBuilder {
+        SnowflakePoolBuilder::new(api)
+    }
+
+    /// Lease an idle session or create a new one, waits up to the acquire timeout
+    /// if the pool is exhausted
+    pub async fn get(&self) -> Result<PooledSession, SnowflakeApiError> {
+        let inner = &self.inner;
+        let permit = tokio::time::timeout(
+            inner.acquire_timeout,
+            Arc::clone(&inner.semaphore).acquire_owned(),
+        )
+        .await
+        .map_err(|_| SnowflakeApiError::PoolTimeout(inner.acquire_timeout))?
+        .expect("pool semaphore is never closed");
+
+        inner.close_idle();
+
+        while let Some((api, since)) = inner.pop_idle() {
+            let needs_check = inner
+                .health_check_after
+                .is_some_and(|after| since.elapsed() > after);
+            if needs_check {
+                if let Err(e) = api.exec_raw(HEALTH_CHECK_QUERY).await {
+                    log::warn!("Dropping unhealthy pooled session: {e}");
+                    inner.state().size -= 1;
+                    // failure could be transient, so the session might still be alive
+                    close_in_background(vec![api]);
+                    continue;
+                }
+            }
+
+            return Ok(PooledSession::new(api, Arc::clone(inner), permit));
+        }
+
+        let api = inner.new_session();
+        Ok(PooledSession::new(api, Arc::clone(inner), permit))
+    }
+
+    pub fn status(&self) -> PoolStatus {
+        let state = self.inner.state();
+        PoolStatus {
+            size: state.size,
+            idle: state.idle.len(),
+            max_size: self.inner.max_size,
+        }
+    }
+}
+
+struct PoolInner {
+    auth: AuthArgs,
+    connection: Arc<Connection>,
+    account_identifier: AccountIdentifier,
+    state: Mutex<PoolState>,
+    // one permit per leased session
+    semaphore: Arc<Semaphore>,
+    min_size: usize,
+    max_size: usize,
+    acquire_timeout: Duration,
+    idle_timeout: Option<Duration>,
+    health_check_after: Option<Duration>,
+}
+
+struct PoolState {
+    // most recently used sessions are at the back, so the ones at the front age out
+    idle: VecDeque<(SnowflakeApi, Instant)>,
+    size: usize,
+}
+
+impl PoolInner {
+    fn state(&self) -> MutexGuard<'_, PoolState> {
+        // state is always consistent, even if other thread panicked
+        self.state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    fn new_session(&self) -> SnowflakeApi {
+        let session = self
+            .auth
+            .clone()
+            .into_session(&self.connection, &self.account_identifier);
+        self.state().size += 1;
+        SnowflakeApi::new(
+            Arc::clone(&self.connection),
+            session,
+            self.account_identifier.clone(),
+        )
+    }
+
+    fn pop_idle(&self) -> Option<(SnowflakeApi, Instant)> {
+        self.state().idle.pop_back()
+    }
+
+    fn release(&self, api: SnowflakeApi) {
+        self.state().idle.push_back((api, Instant::now()));
+        self.close_idle();
+    }
+
+    /// Evict sessions idle for too long and close them in the background
+    fn close_idle(&self) {
+        close_in_background(self.evict_idle());
+    }
+
+    fn evict_idle(&self) -> Vec<SnowflakeApi> {
+        let Some(idle_timeout) = self.idle_timeout else {
+            return vec![];
+        };
+
+        let mut state = self.state();
+        let mut evicted = vec![];
+        while state.size > self.min_size {
+            match state.idle.front() {
+                Some((_, since)) if since.elapsed() > idle_timeout => {
+                    let (api, _) = state.idle.pop_front().unwrap();
+                    evicted.push(api);
+                    state.size -= 1;
+                }
+                _ => break,
+            }
+        }
+        evicted
+    }
+}
+
+/// Best-effort close of the sessions removed from the pool, so they don't linger
+/// on the server until they expire
+fn close_in_background(sessions: Vec<SnowflakeApi>) {
+    if sessions.is_empty() {
+        return;
+    }
+
+    let Ok(handle) = tokio::runtime::Handle::try_current() else {
+        log::debug!("No runtime to close pooled sessions, leaving them to expire");
+        return;
+    };
+    for api in sessions {
+        handle.spawn(async move {
+            if let Err(e) = api.close_session().await {
+                log::debug!("Failed to close pooled session: {e}");
+            }
+        });
+    }
+}
+
+/// Periodically closes idle sessions until the pool is dropped
+fn spawn_reaper(inner: &Arc<PoolInner>, idle_timeout: Duration) {
+    let Ok(handle) = tokio::runtime::Handle::try_current() else {
+        return;
+    };
+
+    let pool = Arc::downgrade(inner);
+    handle.spawn(async move {
+        let mut interval = tokio::time::interval((idle_timeout / 2).max(MIN_REAP_INTERVAL));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let Some(inner) = pool.upgrade() else {
+                break;
+            };
+            inner.close_idle();
+        }
+    });
+}
+
+/// Session leased from [`SnowflakePool`], returned to the pool once dropped.
+/// Clones of the dereferenced [`SnowflakeApi`] share the session, so they must not
+/// outlive the lease, otherwise they would run queries in the session leased to someone else.
+pub struct PooledSession {
+    api: Option<SnowflakeApi>,
+    pool: Arc<PoolInner>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl PooledSession {
+    fn new(api: SnowflakeApi, pool: Arc<PoolInner>, permit: OwnedSemaphorePermit) -> Self {
+        Self {
+            api: Some(api),
+            pool,
+            _permit: permit,
+        }
+    }
+}
+
+impl Deref for PooledSession {
+    type Target = SnowflakeApi;
+
+    fn deref(&self) -> &SnowflakeApi {
+        self.api.as_ref().unwrap()
+    }
+}
+
+impl Drop for PooledSession {
+    fn drop(&mut self) {
+        if let Some(api) = self.api.take() {
+            self.pool.release(api);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicI64, Ordering};
+
+    use super::*;
+    use crate::test_server::{
+        close_response, login_response, query_error_response, query_response, Request, Response,
+        TestServer,
+    };
+
+    const LOGIN_PATH: &str = "/session/v1/login-request";
+    const CLOSE_PATH: &str = "/session";
+
+    /// Every login starts a new session, queries containing `fail` are rejected
+    fn snowflake() -> impl Fn(&Request) -> Response {
+        let sessions = AtomicI64::new(0);
+        move |req| match req.path.as_str() {
+            LOGIN_PATH => {
+                Response::json(&login_response(sessions.fetch_add(1, Ordering::SeqCst) + 1))
+            }
+            "/queries/v1/query-request" => {
+                let sql = req.json()["sqlText"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_owned();
+                if sql.contains("fail") || sql == HEALTH_CHECK_QUERY {
+                    Response::json(&query_error_response("002003", "failed"))
+                } else {
+                    Response::json(&query_response(&sql))
+                }
+            }
+            CLOSE_PATH => Response::json(&close_response()),
+            _ => Response::not_found(),
+        }
+    }
+
+    fn pool(server: &TestServer) -> SnowflakePoolBuilder {
+        SnowflakePool::builder(server.api_builder()).with_health_check_after(None)
+    }
+
+    fn count(server: &TestServer, path: &str) -> usize {
+        server.paths().iter().filter(|p| *p == path).count()
+    }
+
+    fn status(size: usize, idle: usize, max_size: usize) -> PoolStatus {
+        PoolStatus {
+            size,
+            idle,
+            max_size,
+        }
+    }
+
+    #[tokio::test]
+    async fn sessions_are_reused() {
+        let server = TestServer::start(snowflake()).await;
+        let pool = pool(&server).with_max_size(2).build().unwrap();
+        // sessions log in lazily
+        assert_eq!(pool.status(), status(1, 1, 2));
+        assert!(server.requests().is_empty());
+
+        let first = pool.get().await.unwrap();
+        first.exec_raw("SELECT 'first'").await.unwrap();
+        assert_eq!(pool.status(), status(1, 0, 2));
+
+        let second = pool.get().await.unwrap();
+        second.exec_raw("SELECT 'second'").await.unwrap();
+        assert_eq!(pool.status(), status(2, 0, 2));
+
+        drop(first);
+        assert_eq!(pool.status(), status(2, 1, 2));
+        drop(second);
+        assert_eq!(pool.status(), status(2, 2, 2));
+
+        let third = pool.get().await.unwrap();
+        third.exec_raw("SELECT 'third'").await.unwrap();
+        assert_eq!(pool.status(), status(2, 1, 2));
+        assert_eq!(count(&server, LOGIN_PATH), 2);
+    }
+
+    #[tokio::test]
+    async fn get_times_out_when_exhausted() {
+        let server = TestServer::start(snowflake()).await;
+        let timeout = Duration::from_millis(50);
+        let pool = pool(&server)
+            .with_max_size(1)
+            .with_acquire_timeout(timeout)
+            .build()
+            .unwrap();
+
+        let leased = pool.get().await.unwrap();
+        assert!(matches!(
+            pool.get().await,
+            Err(SnowflakeApiError::PoolTimeout(t)) if t == timeout
+        ));
+
+        drop(leased);
+        let _leased = pool.get().await.unwrap();
+        assert_eq!(pool.status(), status(1, 0, 1));
+    }
+
+    #[tokio::test]
+    async fn idle_sessions_are_closed_in_background() {
+        let server = TestServer::start(snowflake()).await;
+        let pool = pool(&server)
+            .with_min_size(1)
+            .with_idle_timeout(Some(Duration::from_millis(50)))
+            .build()
+            .unwrap();
+
+        let first = pool.get().await.unwrap();
+        let second = pool.get().await.unwrap();
+        first.exec_raw("SELECT 'first'").await.unwrap();
+        second.exec_raw("SELECT 'second'").await.unwrap();
+        drop(first);
+        drop(second);
+        assert_eq!(pool.status(), status(2, 2, DEFAULT_MAX_SIZE));
+
+        // nothing is leased or returned, so only the reaper could close the session
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert_eq!(pool.status(), status(1, 1, DEFAULT_MAX_SIZE));
+        assert_eq!(count(&server, CLOSE_PATH), 1);
+    }
+
+    #[tokio::test]
+    async fn unhealthy_sessions_are_replaced() {
+        let server = TestServer::start(snowflake()).await;
+        let pool = pool(&server)
+            .with_min_size(0)
+            .with_health_check_after(Some(Duration::ZERO))
+            .build()
+            .unwrap();
+
+        let leased = pool.get().await.unwrap();
+        leased.exec_raw("SELECT 'first'").await.unwrap();
+        drop(leased);
+
+        // health check fails, so the session is closed and a new one is logged in
+        let leased = pool.get().await.unwrap();
+        assert_eq!(pool.status(), status(1, 0, DEFAULT_MAX_SIZE));
+        leased.exec_raw("SELECT 'second'").await.unwrap();
+        assert_eq!(count(&server, LOGIN_PATH), 2);
+
+        // session is closed in the background
+        for _ in 0..50 {
+            if count(&server, CLOSE_PATH) > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(count(&server, CLOSE_PATH), 1);
+    }
+}
@@ -17,6 +17,12 @@ pub struct LoginRequest<T> {
 pub type PasswordLoginRequest = LoginRequest<PasswordRequestData>;
 #[cfg(feature = "cert-auth")]
 pub type CertLoginRequest = LoginRequest<CertRequestData>;
+pub type MfaLoginRequest = LoginRequest<MfaRequestData>;
+pub type SamlLoginRequest = LoginRequest<SamlRequestData>;
+pub type ExternalBrowserLoginRequest = LoginRequest<ExternalBrowserRequestData>;
+pub type AuthenticatorLoginRequest = LoginRequest<AuthenticatorRequestData>;
+/// Used by the authenticators which send a single token, eg OAuth or programmatic access token
+pub type TokenLoginRequest = LoginRequest<TokenRequestData>;
 
 #[derive(Serialize, Debug)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
@@ -34,6 +40,8 @@ pub struct LoginRequestCommon {
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub struct SessionParameters {
     pub client_validate_default_parameters: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_request_mfa_token: Option<bool>,
 }
 
 #[derive(Serialize, Debug)]
@@ -53,6 +61,7 @@ pub struct PasswordRequestData {
     pub password: String,
 }
 
+#[cfg(feature = "cert-auth")]
 #[derive(Serialize, Debug)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub struct CertRequestData {
@@ -62,6 +71,68 @@ pub struct CertRequestData {
     pub token: String,
 }
 
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub struct TokenRequestData {
+    #[serde(flatten)]
+    pub login_request_common: LoginRequestCommon,
+    pub authenticator: String,
+    pub token: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub struct ExternalBrowserRequestData {
+    #[serde(flatten)]
+    pub login_request_common: LoginRequestCommon,
+    pub authenticator: String,
+    pub token: String,
+    pub proof_key: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub struct MfaRequestData {
+    #[serde(flatten)]
+    pub login_request_common: LoginRequestCommon,
+    pub authenticator: String,
+    pub password: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub passcode: Option<String>,
+    // either `passcode` or `push`
+    pub ext_authn_duo_method: String,
+    // cached MFA token, allows to skip the prompt
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub struct SamlRequestData {
+    #[serde(flatten)]
+    pub login_request_common: LoginRequestCommon,
+    #[serde(rename = "RAW_SAML_RESPONSE")]
+    pub raw_saml_response: String,
+}
+
+/// Native Okta authentication, sent directly to the identity provider
+#[derive(Serialize, Debug)]
+pub struct OktaTokenRequest {
+    pub username: String,
+    pub password: String,
+}
+
+/// Asks Snowflake for the identity provider urls before the actual login
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub struct AuthenticatorRequestData {
+    #[serde(flatten)]
+    pub login_request_common: LoginRequestCommon,
+    pub authenticator: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub browser_mode_redirect_port: Option<String>,
+}
+
 #[derive(Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct RenewSessionRequest {
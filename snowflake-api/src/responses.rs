@@ -88,12 +88,12 @@ pub struct LoginResponseData {
     pub session_info: SessionInfo,
     pub master_validity_in_seconds: i64,
     pub validity_in_seconds: i64,
+    // only present when MFA token was requested
+    pub mfa_token: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
-// FIXME: dead_code
-#[allow(dead_code)]
 pub struct SessionInfo {
     pub database_name: Option<String>,
     pub schema_name: Option<String>,
@@ -103,12 +103,20 @@ pub struct SessionInfo {
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
-// FIXME: dead_code
-#[allow(dead_code)]
 pub struct AuthenticatorResponseData {
-    pub token_url: String,
+    // only present for native Okta authenticator
+    pub token_url: Option<String>,
     pub sso_url: String,
-    pub proof_key: String,
+    // only present for external browser authenticator
+    pub proof_key: Option<String>,
+}
+
+/// Native Okta authentication response, either of the tokens is used as a one-time token
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct OktaTokenResponse {
+    pub session_token: Option<String>,
+    pub cookie_token: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
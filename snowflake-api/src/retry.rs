@@ -0,0 +1,354 @@
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use http::Extensions;
+use rand::Rng;
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Request, Response, StatusCode};
+use reqwest_middleware::{Middleware, Next};
+use url::Url;
+use uuid::Uuid;
+
+pub const DEFAULT_MAX_RETRIES: u32 = 5;
+/// Same as the login timeout in the official drivers
+pub const DEFAULT_RETRY_DEADLINE: Duration = Duration::from_secs(5 * 60);
+const BASE_DELAY: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_secs(16);
+
+/// Retries transient failures the way official drivers do. `requestId` is kept, so Snowflake
+/// de-duplicates the retried query, while `request_guid` is regenerated on every attempt
+/// and `retryCount`/`retryReason` are added. Requests to other hosts, eg chunk downloads
+/// from the stage, are retried as is.
+#[derive(Debug, Clone)]
+pub struct SnowflakeRetryMiddleware {
+    max_retries: u32,
+    deadline: Duration,
+}
+
+impl Default for SnowflakeRetryMiddleware {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_MAX_RETRIES,
+            deadline: DEFAULT_RETRY_DEADLINE,
+        }
+    }
+}
+
+impl SnowflakeRetryMiddleware {
+    #[must_use]
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// No retries are made once the deadline is reached, counting from the first attempt
+    #[must_use]
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    fn backoff(attempt: u32) -> Duration {
+        let delay = BASE_DELAY
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(MAX_DELAY);
+        // full jitter, so clients throttled at the same time don't retry together
+        let millis = u64::try_from(delay.as_millis()).unwrap_or(u64::MAX);
+        Duration::from_millis(rand::thread_rng().gen_range(0..=millis))
+    }
+}
+
+/// Why the request should be retried, reported as `retryReason`
+enum RetryReason {
+    Status(StatusCode, Option<Duration>),
+    Network,
+}
+
+impl RetryReason {
+    fn from_result(res: &reqwest_middleware::Result<Response>) -> Option<Self> {
+        match res {
+            Ok(resp) => {
+                let status = resp.status();
+                let retryable = status == StatusCode::TOO_MANY_REQUESTS
+                    || status == StatusCode::REQUEST_TIMEOUT
+                    || (status.is_server_error() && status != StatusCode::NOT_IMPLEMENTED);
+                retryable.then(|| {
+                    let retry_after = resp
+                        .headers()
+                        .get(RETRY_AFTER)
+                        .and_then(|v| v.to_str().ok())
+                        .and_then(|v| v.trim().parse().ok())
+                        .map(Duration::from_secs);
+                    RetryReason::Status(status, retry_after)
+                })
+            }
+            Err(reqwest_middleware::Error::Reqwest(e))
+                if e.is_timeout() || e.is_connect() || e.is_request() =>
+            {
+                Some(RetryReason::Network)
+            }
+            Err(_) => None,
+        }
+    }
+
+    fn code(&self) -> String {
+        match self {
+            RetryReason::Status(status, _) => status.as_u16().to_string(),
+            RetryReason::Network => "0".to_owned(),
+        }
+    }
+}
+
+/// Only Snowflake API requests carry `request_guid`
+fn is_snowflake_request(url: &Url) -> bool {
+    url.query_pairs().any(|(k, _)| k == "request_guid")
+}
+
+fn update_retry_params(url: &mut Url, retry_count: u32, reason: &RetryReason) {
+    let params: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(k, _)| k != "request_guid" && k != "retryCount" && k != "retryReason")
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+
+    url.query_pairs_mut()
+        .clear()
+        .extend_pairs(params)
+        .append_pair("request_guid", &Uuid::new_v4().to_string())
+        .append_pair("retryCount", &retry_count.to_string())
+        .append_pair("retryReason", &reason.code());
+}
+
+#[async_trait]
+impl Middleware for SnowflakeRetryMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        let started = Instant::now();
+        let mut retry_count = 0;
+        let mut req = req;
+
+        loop {
+            // streaming bodies can't be retried
+            let Some(retry_req) = req.try_clone() else {
+                return next.run(req, extensions).await;
+            };
+
+            let res = next.clone().run(retry_req, extensions).await;
+            let Some(reason) = RetryReason::from_result(&res) else {
+                return res;
+            };
+
+            let delay = match &reason {
+                RetryReason::Status(_, Some(retry_after)) => *retry_after,
+                _ => Self::backoff(retry_count),
+            };
+            if retry_count >= self.max_retries || started.elapsed() + delay > self.deadline {
+                return res;
+            }
+
+            retry_count += 1;
+            log::debug!(
+                "Retrying request, attempt {retry_count}, reason {}, in {delay:?}",
+                reason.code()
+            );
+            tokio::time::sleep(delay).await;
+
+            if is_snowflake_request(req.url()) {
+                update_retry_params(req.url_mut(), retry_count, &reason);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::account::AccountIdentifier;
+    use crate::connection::{ClientOptions, Connection, ConnectionError, QueryType};
+    use crate::test_server::{query_response, Request, Response, TestServer};
+
+    /// First `failures` requests are answered with `failure`, the rest succeed
+    async fn flaky_server(failures: usize, failure: fn() -> Response) -> TestServer {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        TestServer::start(move |_: &Request| {
+            if attempts.fetch_add(1, Ordering::SeqCst) < failures {
+                failure()
+            } else {
+                Response::json(&query_response("ok"))
+            }
+        })
+        .await
+    }
+
+    fn connection(server: &TestServer, retry: SnowflakeRetryMiddleware) -> Connection {
+        server.connection_with(&ClientOptions {
+            retry,
+            ..ClientOptions::default()
+        })
+    }
+
+    async fn query(connection: &Connection) -> Result<Value, ConnectionError> {
+        let account = AccountIdentifier::parse("org-account").unwrap();
+        connection
+            .request(QueryType::JsonQuery, &account, &[], None, json!({}))
+            .await
+    }
+
+    /// Query parameters of the request, repeated ones are kept
+    fn params(request: &Request) -> Vec<(String, String)> {
+        url::form_urlencoded::parse(request.query.as_bytes())
+            .into_owned()
+            .collect()
+    }
+
+    fn param<'a>(params: &'a [(String, String)], name: &str) -> Vec<&'a str> {
+        params
+            .iter()
+            .filter(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn retried_request_params() {
+        let server =
+            flaky_server(2, || Response::status(503).with_header("Retry-After", "0")).await;
+
+        query(&connection(&server, SnowflakeRetryMiddleware::default()))
+            .await
+            .unwrap();
+
+        let attempts: Vec<_> = server.requests().iter().map(params).collect();
+        assert_eq!(attempts.len(), 3);
+
+        let request_ids: Vec<_> = attempts.iter().map(|p| param(p, "requestId")).collect();
+        assert_eq!(request_ids[0].len(), 1);
+        assert!(
+            request_ids.iter().all(|id| *id == request_ids[0]),
+            "{request_ids:?}"
+        );
+
+        let guids: HashSet<_> = attempts
+            .iter()
+            .map(|p| param(p, "request_guid")[0])
+            .collect();
+        assert_eq!(guids.len(), 3);
+        assert!(attempts.iter().all(|p| param(p, "request_guid").len() == 1));
+
+        assert!(param(&attempts[0], "retryCount").is_empty());
+        assert!(param(&attempts[0], "retryReason").is_empty());
+        for (retry_count, attempt) in attempts.iter().enumerate().skip(1) {
+            assert_eq!(param(attempt, "retryCount"), [retry_count.to_string()]);
+            assert_eq!(param(attempt, "retryReason"), ["503"]);
+        }
+    }
+
+    #[tokio::test]
+    async fn retry_after_is_honored() {
+        let server =
+            flaky_server(1, || Response::status(429).with_header("Retry-After", "1")).await;
+
+        let started = Instant::now();
+        query(&connection(&server, SnowflakeRetryMiddleware::default()))
+            .await
+            .unwrap();
+
+        assert!(started.elapsed() >= Duration::from_secs(1));
+        assert_eq!(server.requests().len(), 2);
+        let retry = params(&server.requests()[1]);
+        assert_eq!(param(&retry, "retryReason"), ["429"]);
+    }
+
+    #[tokio::test]
+    async fn deadline_stops_retries() {
+        let server = flaky_server(usize::MAX, || {
+            Response::status(429).with_header("Retry-After", "60")
+        })
+        .await;
+        let retry = SnowflakeRetryMiddleware::default().with_deadline(Duration::from_secs(1));
+
+        let started = Instant::now();
+        let err = query(&connection(&server, retry)).await.unwrap_err();
+
+        // delay would exceed the deadline, so the error is returned right away
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(matches!(err, ConnectionError::Throttled { .. }), "{err:?}");
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn max_retries_stops_retries() {
+        let server = flaky_server(usize::MAX, || {
+            Response::status(502).with_header("Retry-After", "0")
+        })
+        .await;
+        let retry = SnowflakeRetryMiddleware::default().with_max_retries(2);
+
+        let err = query(&connection(&server, retry)).await.unwrap_err();
+
+        assert!(
+            matches!(err, ConnectionError::ServiceUnavailable { .. }),
+            "{err:?}"
+        );
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_not_retried() {
+        let server = flaky_server(1, || Response::status(400)).await;
+
+        let err = query(&connection(&server, SnowflakeRetryMiddleware::default()))
+            .await
+            .unwrap_err();
+
+        assert!(
+            matches!(err, ConnectionError::UnexpectedStatus { .. }),
+            "{err:?}"
+        );
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn other_requests_are_retried_as_is() {
+        let server =
+            flaky_server(1, || Response::status(503).with_header("Retry-After", "0")).await;
+        let url = format!(
+            "{}/stage/chunk?x-amz-signature=abc&retryCount=5",
+            server.url()
+        );
+
+        connection(&server, SnowflakeRetryMiddleware::default())
+            .get_chunk(&url, &HashMap::new())
+            .await
+            .unwrap();
+
+        let queries: Vec<_> = server.requests().into_iter().map(|r| r.query).collect();
+        assert_eq!(
+            queries,
+            [
+                "x-amz-signature=abc&retryCount=5",
+                "x-amz-signature=abc&retryCount=5"
+            ]
+        );
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        for attempt in [0, 1, 4, 10, u32::MAX] {
+            let cap = BASE_DELAY
+                .saturating_mul(2u32.saturating_pow(attempt))
+                .min(MAX_DELAY);
+            assert!(SnowflakeRetryMiddleware::backoff(attempt) <= cap);
+        }
+    }
+}
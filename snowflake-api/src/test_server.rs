@@ -0,0 +1,372 @@
+//! Local HTTP server standing in for Snowflake and identity providers in the tests
+
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::connection::{ClientOptions, Connection, Endpoint};
+use crate::{AuthArgs, AuthType, PasswordArgs, SnowflakeApiBuilder};
+
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    /// Path without the query string
+    pub path: String,
+    pub query: String,
+    /// `Host` header, so responses can link back to the server
+    pub host: String,
+    pub body: String,
+}
+
+impl Request {
+    pub fn json(&self) -> Value {
+        serde_json::from_str(&self.body).unwrap_or(Value::Null)
+    }
+}
+
+pub struct Response {
+    status: u16,
+    content_type: &'static str,
+    headers: Vec<(&'static str, String)>,
+    body: String,
+    /// Response is sent after the delay, to simulate a stalled server
+    delay: Duration,
+}
+
+impl Response {
+    pub fn json(body: &Value) -> Self {
+        Self {
+            status: 200,
+            content_type: "application/json",
+            headers: vec![],
+            delay: Duration::ZERO,
+            body: body.to_string(),
+        }
+    }
+
+    pub fn html(body: impl Into<String>) -> Self {
+        Self {
+            status: 200,
+            content_type: "text/html",
+            headers: vec![],
+            delay: Duration::ZERO,
+            body: body.into(),
+        }
+    }
+
+    /// Empty response with the status
+    pub fn status(status: u16) -> Self {
+        Self {
+            status,
+            content_type: "text/plain",
+            headers: vec![],
+            delay: Duration::ZERO,
+            body: String::new(),
+        }
+    }
+
+    pub fn not_found() -> Self {
+        Self::status(404)
+    }
+
+    #[must_use]
+    pub fn with_status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    #[must_use]
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    #[must_use]
+    pub fn with_header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+}
+
+/// Environment is shared by the test threads
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+/// Exclusive access to the environment, `SNOWFLAKE_*` variables are cleared
+/// when it's taken and released
+pub struct TestEnv {
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl TestEnv {
+    pub fn new() -> Self {
+        let lock = ENV_LOCK
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        clear_env();
+        Self { _lock: lock }
+    }
+}
+
+impl Drop for TestEnv {
+    fn drop(&mut self) {
+        clear_env();
+    }
+}
+
+fn clear_env() {
+    for (key, _) in std::env::vars() {
+        if key.starts_with("SNOWFLAKE_") {
+            std::env::remove_var(key);
+        }
+    }
+}
+
+/// Key generation is slow in debug builds, so the keys are shared by the tests
+#[cfg(feature = "cert-auth")]
+pub fn test_key() -> &'static snowflake_jwt::KeyPair {
+    static KEY: std::sync::OnceLock<snowflake_jwt::KeyPair> = std::sync::OnceLock::new();
+    KEY.get_or_init(|| snowflake_jwt::KeyPair::generate().unwrap())
+}
+
+/// Key different from [`test_key`], eg the one which the key is rotated to
+#[cfg(feature = "cert-auth")]
+pub fn other_test_key() -> &'static snowflake_jwt::KeyPair {
+    static KEY: std::sync::OnceLock<snowflake_jwt::KeyPair> = std::sync::OnceLock::new();
+    KEY.get_or_init(|| snowflake_jwt::KeyPair::generate().unwrap())
+}
+
+type Handler = dyn Fn(&Request) -> Response + Send + Sync;
+
+pub struct TestServer {
+    pub port: u16,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl TestServer {
+    pub async fn start(handler: impl Fn(&Request) -> Response + Send + Sync + 'static) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let requests = Arc::new(Mutex::new(vec![]));
+        let handler: Arc<Handler> = Arc::new(handler);
+
+        let log = Arc::clone(&requests);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let handler = Arc::clone(&handler);
+                let log = Arc::clone(&log);
+                tokio::spawn(async move { serve(stream, handler.as_ref(), &log).await });
+            }
+        });
+
+        Self { port, requests }
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://127.0.0.1:{}", self.port)
+    }
+
+    /// Connection sending all Snowflake requests to this server
+    pub fn connection(&self) -> Connection {
+        self.connection_with(&ClientOptions::default())
+    }
+
+    /// Same as [`TestServer::connection`], with the client configured by `options`
+    pub fn connection_with(&self, options: &ClientOptions) -> Connection {
+        let client = Connection::client_builder(options).unwrap().build();
+        Connection::new_with_middware(client).with_endpoint(Endpoint {
+            host: Some("127.0.0.1".to_owned()),
+            port: Some(self.port),
+            protocol: Some("http".to_owned()),
+        })
+    }
+
+    /// API with password auth, sending all requests to this server
+    pub fn api_builder(&self) -> SnowflakeApiBuilder {
+        let auth = AuthArgs {
+            account_identifier: "org-account".to_owned(),
+            warehouse: None,
+            database: None,
+            schema: None,
+            username: "user".to_owned(),
+            role: None,
+            auth_type: AuthType::Password(PasswordArgs {
+                password: "password".into(),
+            }),
+        };
+        SnowflakeApiBuilder::new(auth)
+            .with_host("127.0.0.1")
+            .with_port(self.port)
+            .with_protocol("http")
+    }
+
+    /// Requests served so far, in the order they were handled
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    pub fn paths(&self) -> Vec<String> {
+        self.requests().into_iter().map(|r| r.path).collect()
+    }
+}
+
+async fn serve(mut stream: TcpStream, handler: &Handler, log: &Mutex<Vec<Request>>) -> Option<()> {
+    let mut buf = Vec::new();
+    let head_end = loop {
+        let mut chunk = [0u8; 4096];
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..head_end]).into_owned();
+    let header = |name: &str| {
+        head.lines()
+            .filter_map(|l| l.split_once(':'))
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.trim().to_owned())
+    };
+    let content_length = header("content-length")
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(0);
+    while buf.len() < head_end + content_length {
+        let mut chunk = [0u8; 4096];
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+
+    let mut request_line = head.lines().next()?.split_whitespace();
+    let method = request_line.next()?.to_owned();
+    let target = request_line.next()?;
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let req = Request {
+        method,
+        path: path.to_owned(),
+        query: query.to_owned(),
+        host: header("host").unwrap_or_default(),
+        body: String::from_utf8_lossy(&buf[head_end..]).into_owned(),
+    };
+
+    let resp = handler(&req);
+    // logged before responding, so the client never sees a response of unlogged request
+    log.lock().unwrap().push(req);
+    tokio::time::sleep(resp.delay).await;
+    let mut head = format!(
+        "HTTP/1.1 {} X\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        resp.status,
+        resp.content_type,
+        resp.body.len()
+    );
+    for (name, value) in &resp.headers {
+        write!(head, "{name}: {value}\r\n").unwrap();
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes()).await.ok()?;
+    stream.write_all(resp.body.as_bytes()).await.ok()?;
+    stream.shutdown().await.ok()
+}
+
+/// Successful login response
+pub fn login_response(session_id: i64) -> Value {
+    json!({
+        "data": {
+            "sessionId": session_id,
+            "token": format!("session-{session_id}"),
+            "masterToken": format!("master-{session_id}"),
+            "serverVersion": "9.0.0",
+            "parameters": [],
+            "sessionInfo": {
+                "databaseName": "DB",
+                "schemaName": "PUBLIC",
+                "warehouseName": "WH",
+                "roleName": "ROLE"
+            },
+            "masterValidityInSeconds": 14400,
+            "validityInSeconds": 3600
+        },
+        "code": null,
+        "message": null,
+        "success": true
+    })
+}
+
+/// Failed login with the Snowflake error code
+pub fn login_error_response(code: &str, message: &str) -> Value {
+    json!({
+        "data": {
+            "authnMethod": "PASSWORD",
+            "errorCode": code
+        },
+        "code": code,
+        "message": message,
+        "success": false
+    })
+}
+
+/// Successful query returning a single text value
+pub fn query_response(value: &str) -> Value {
+    json!({
+        "data": {
+            "parameters": [],
+            "rowtype": [{
+                "name": "VALUE",
+                "type": "text",
+                "nullable": true,
+                "byteLength": null,
+                "length": null,
+                "scale": null,
+                "precision": null
+            }],
+            "rowset": [[value]],
+            "total": 1,
+            "returned": 1,
+            "queryId": "query-id",
+            "finalDatabaseName": "DB",
+            "finalSchemaName": "PUBLIC",
+            "finalWarehouseName": "WH",
+            "finalRoleName": "ROLE",
+            "statementTypeId": 1,
+            "version": 1
+        },
+        "code": null,
+        "message": null,
+        "success": true
+    })
+}
+
+/// Failed query with the Snowflake error code
+pub fn query_error_response(code: &str, message: &str) -> Value {
+    json!({
+        "data": {
+            "age": 0,
+            "errorCode": code,
+            "internalError": false,
+            "sqlState": "02000",
+            "queryId": "query-id"
+        },
+        "code": code,
+        "message": message,
+        "success": false
+    })
+}
+
+/// Successful close session response
+pub fn close_response() -> Value {
+    json!({
+        "data": null,
+        "code": null,
+        "message": null,
+        "success": true
+    })
+}
@@ -0,0 +1,317 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tokio::sync::{Mutex, MutexGuard};
+
+use crate::{QueryResult, RawQueryResult, SnowflakeApi, SnowflakeApiError};
+
+/// Explicit transaction started with [`SnowflakeApi::begin`]. Transactions are scoped
+/// to the session, so statements sent through other clones of the API in the meantime
+/// are a part of it too, use [`crate::SnowflakePool`] to run transactions concurrently.
+///
+/// Transaction is rolled back if dropped without commit. `ROLLBACK` is sent in the background,
+/// or before the next statement if there is no runtime. Statements sent through the API
+/// after the drop wait for the rollback to complete, so they never run inside of
+/// the abandoned transaction.
+#[must_use = "transaction is rolled back if dropped without commit"]
+pub struct Transaction {
+    api: SnowflakeApi,
+    finished: bool,
+}
+
+impl Transaction {
+    pub(crate) async fn begin(api: SnowflakeApi) -> Result<Self, SnowflakeApiError> {
+        api.exec_raw("BEGIN").await?;
+        Ok(Self {
+            api,
+            finished: false,
+        })
+    }
+
+    pub async fn exec(&self, sql: &str) -> Result<QueryResult, SnowflakeApiError> {
+        self.api.exec(sql).await
+    }
+
+    pub async fn exec_raw(&self, sql: &str) -> Result<RawQueryResult, SnowflakeApiError> {
+        self.api.exec_raw(sql).await
+    }
+
+    /// Transaction is rolled back as if dropped if `COMMIT` fails,
+    /// as it's unknown whether the session is still inside of it
+    pub async fn commit(mut self) -> Result<(), SnowflakeApiError> {
+        self.api.exec_raw("COMMIT").await?;
+        self.finished = true;
+        Ok(())
+    }
+
+    /// Failed `ROLLBACK` is retried as if the transaction was dropped
+    pub async fn rollback(mut self) -> Result<(), SnowflakeApiError> {
+        self.api.exec_raw("ROLLBACK").await?;
+        self.finished = true;
+        Ok(())
+    }
+}
+
+/// Transaction passed to the [`SnowflakeApi::transaction`] closure, which commits
+/// or rolls it back once the closure completes. Handle could be cloned, eg to run statements
+/// from several futures, but clones must not be used after the closure has completed.
+#[derive(Clone)]
+pub struct TransactionHandle {
+    api: SnowflakeApi,
+}
+
+impl TransactionHandle {
+    pub(crate) fn new(tx: &Transaction) -> Self {
+        Self {
+            api: tx.api.clone(),
+        }
+    }
+
+    pub async fn exec(&self, sql: &str) -> Result<QueryResult, SnowflakeApiError> {
+        self.api.exec(sql).await
+    }
+
+    pub async fn exec_raw(&self, sql: &str) -> Result<RawQueryResult, SnowflakeApiError> {
+        self.api.exec_raw(sql).await
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+
+        log::debug!("Transaction dropped without commit, rolling back");
+        self.api.pending_rollback.set();
+        // without a runtime rollback is sent before the next statement
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            let api = self.api.clone();
+            handle.spawn(async move {
+                if let Err(e) = api.rollback_if_pending().await {
+                    log::warn!("Failed to roll back dropped transaction: {e}");
+                }
+            });
+        }
+    }
+}
+
+/// Rollback of the dropped transaction, shared by the API clones. Flag is set synchronously
+/// on drop, while the lock is held from checking the flag until `ROLLBACK` completes,
+/// and the flag is only cleared once it succeeds.
+#[derive(Default)]
+pub(crate) struct PendingRollback {
+    pending: AtomicBool,
+    lock: Mutex<()>,
+}
+
+impl PendingRollback {
+    fn set(&self) {
+        self.pending.store(true, Ordering::SeqCst);
+    }
+
+    pub(crate) async fn lock(&self) -> MutexGuard<'_, ()> {
+        self.lock.lock().await
+    }
+
+    pub(crate) fn is_pending(&self) -> bool {
+        self.pending.load(Ordering::SeqCst)
+    }
+
+    /// Must be called with the lock held, once `ROLLBACK` has succeeded
+    pub(crate) fn clear(&self) {
+        self.pending.store(false, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::test_server::{
+        login_response, query_error_response, query_response, Request, Response, TestServer,
+    };
+
+    const QUERY_PATH: &str = "/queries/v1/query-request";
+
+    /// `ROLLBACK` is slow, so a statement which doesn't wait for it would be answered first
+    fn snowflake(req: &Request) -> Response {
+        match req.path.as_str() {
+            "/session/v1/login-request" => Response::json(&login_response(1)),
+            QUERY_PATH => {
+                let sql = req.json()["sqlText"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_owned();
+                if sql == "ROLLBACK" {
+                    std::thread::sleep(Duration::from_millis(200));
+                }
+                if sql.contains("fail") {
+                    Response::json(&query_error_response("002003", "failed"))
+                } else {
+                    Response::json(&query_response(&sql))
+                }
+            }
+            _ => Response::not_found(),
+        }
+    }
+
+    /// Same as [`snowflake`], but the first `sql` statement fails
+    fn failing_once(sql: &'static str) -> impl Fn(&Request) -> Response {
+        let failed = AtomicBool::new(false);
+        move |req| {
+            if req.json()["sqlText"] == sql && !failed.swap(true, Ordering::SeqCst) {
+                Response::json(&query_error_response("000604", "failed"))
+            } else {
+                snowflake(req)
+            }
+        }
+    }
+
+    /// Statements sent to the query endpoint
+    fn statements(server: &TestServer) -> Vec<String> {
+        server
+            .requests()
+            .iter()
+            .filter(|r| r.path == QUERY_PATH)
+            .filter_map(|r| r.json()["sqlText"].as_str().map(str::to_owned))
+            .collect()
+    }
+
+    async fn api(server: &TestServer) -> SnowflakeApi {
+        let api = server.api_builder().build().unwrap();
+        // log in upfront, so only statements are timed
+        api.exec_raw("SELECT 'login'").await.unwrap();
+        api
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn transaction_is_committed_on_ok() {
+        let server = TestServer::start(snowflake).await;
+        let api = api(&server).await;
+
+        let res = api
+            .transaction(|tx| async move { tx.exec_raw("INSERT 1").await.map(|_| 1) })
+            .await;
+
+        assert_eq!(res.unwrap(), 1);
+        assert_eq!(
+            statements(&server),
+            ["SELECT 'login'", "BEGIN", "INSERT 1", "COMMIT"]
+        );
+        assert!(server.paths()[1..].iter().all(|p| p == QUERY_PATH));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn transaction_is_rolled_back_on_err() {
+        let server = TestServer::start(snowflake).await;
+        let api = api(&server).await;
+
+        let res = api
+            .transaction(|tx| async move {
+                tx.exec_raw("INSERT 1").await?;
+                tx.exec_raw("INSERT fail").await
+            })
+            .await;
+
+        assert!(matches!(res, Err(SnowflakeApiError::ApiError(code, _)) if code == "002003"));
+        assert_eq!(
+            statements(&server),
+            [
+                "SELECT 'login'",
+                "BEGIN",
+                "INSERT 1",
+                "INSERT fail",
+                "ROLLBACK"
+            ]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn next_statement_waits_for_rollback_of_dropped_transaction() {
+        let server = TestServer::start(snowflake).await;
+        let api = api(&server).await;
+
+        let tx = api.begin().await.unwrap();
+        tx.exec_raw("INSERT 1").await.unwrap();
+        drop(tx);
+        // give the background rollback a head start, so it holds the lock
+        tokio::task::yield_now().await;
+        api.exec_raw("SELECT 'after'").await.unwrap();
+
+        assert_eq!(
+            statements(&server),
+            [
+                "SELECT 'login'",
+                "BEGIN",
+                "INSERT 1",
+                "ROLLBACK",
+                "SELECT 'after'"
+            ]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn failed_commit_is_rolled_back() {
+        let server = TestServer::start(failing_once("COMMIT")).await;
+        let api = api(&server).await;
+
+        let tx = api.begin().await.unwrap();
+        tx.exec_raw("INSERT 1").await.unwrap();
+        assert!(tx.commit().await.is_err());
+        api.exec_raw("SELECT 'after'").await.unwrap();
+
+        assert_eq!(
+            statements(&server),
+            [
+                "SELECT 'login'",
+                "BEGIN",
+                "INSERT 1",
+                "COMMIT",
+                "ROLLBACK",
+                "SELECT 'after'"
+            ]
+        );
+    }
+
+    #[test]
+    fn failed_rollback_of_dropped_transaction_stays_pending() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let server = runtime.block_on(TestServer::start(failing_once("ROLLBACK")));
+        let api = runtime.block_on(api(&server));
+
+        // without a runtime rollback is sent before the next statement, rather than raced with it
+        let tx = runtime.block_on(api.begin()).unwrap();
+        drop(tx);
+        let res = runtime.block_on(api.exec_raw("SELECT 'failed'"));
+        assert!(matches!(res, Err(SnowflakeApiError::ApiError(code, _)) if code == "000604"));
+        runtime.block_on(api.exec_raw("SELECT 'after'")).unwrap();
+
+        assert_eq!(
+            statements(&server),
+            [
+                "SELECT 'login'",
+                "BEGIN",
+                "ROLLBACK",
+                "ROLLBACK",
+                "SELECT 'after'"
+            ]
+        );
+    }
+
+    #[test]
+    fn dropped_transaction_is_rolled_back_without_runtime() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let server = runtime.block_on(TestServer::start(snowflake));
+        let api = runtime.block_on(api(&server));
+
+        let tx = runtime.block_on(api.begin()).unwrap();
+        drop(tx);
+        runtime.block_on(api.exec_raw("SELECT 'after'")).unwrap();
+
+        assert_eq!(
+            statements(&server),
+            ["SELECT 'login'", "BEGIN", "ROLLBACK", "SELECT 'after'"]
+        );
+    }
+}